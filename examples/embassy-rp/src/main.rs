@@ -0,0 +1,65 @@
+//! The async display driver on embassy-rp
+//!
+//! Drives the Waveshare Pico LCD 2 on a Pico 2 with `AsyncST7789Display` and embassy-rp's
+//! async SPI, which transmits by DMA. Each frame is sent while the next one is rendered, as in
+//! the README's snippet. Build it with `cargo build --release` in this directory; run it with
+//! `cargo run --release` like the firmware.
+
+#![no_std]
+#![no_main]
+
+use embassy_executor::Spawner;
+use embassy_futures::join::join;
+use embassy_rp::block::ImageDef;
+use embassy_rp::gpio::{Level, Output};
+use embassy_rp::spi::{self, Spi};
+use embassy_time::{Delay, Instant};
+use fragments::color::PixelFormat;
+use fragments::display_async::AsyncST7789Display;
+use fragments::framebuffer::{HEIGHT, WIDTH};
+use fragments::panel::St7789;
+use fragments::shader::render_rows;
+use fragments::shaders::Gradient;
+use fragments::uniforms::{FrameClock, InputState};
+use panic_halt as _;
+
+/// Tell the Boot ROM about our application
+#[unsafe(link_section = ".start_block")]
+#[used]
+pub static IMAGE_DEF: ImageDef = ImageDef::secure_exe();
+
+const PIXEL_FORMAT: PixelFormat = PixelFormat::Rgb565;
+const FRAME_SIZE: usize = PIXEL_FORMAT.bytes_for(WIDTH as usize * HEIGHT as usize);
+
+#[embassy_executor::main]
+async fn main(_spawner: Spawner) {
+    let p = embassy_rp::init(Default::default());
+
+    let mut config = spi::Config::default();
+    config.frequency = 62_500_000;
+    let spi = Spi::new_txonly(p.SPI1, p.PIN_10, p.PIN_11, p.DMA_CH0, config);
+    let cs = Output::new(p.PIN_9, Level::High);
+    let dc = Output::new(p.PIN_8, Level::Low);
+    let rst = Output::new(p.PIN_12, Level::High);
+    // Backlight at full brightness
+    let _backlight = Output::new(p.PIN_13, Level::High);
+
+    let mut display = AsyncST7789Display::new(St7789::LCD_240X320, spi, cs, dc, rst).with_pixel_format(PIXEL_FORMAT);
+    while display.init(&mut Delay).await.is_err() {}
+
+    let [front, back] = cortex_m::singleton!(: [[u8; FRAME_SIZE]; 2] = [[0; FRAME_SIZE]; 2]).unwrap();
+    let (mut front, mut back) = (front, back);
+    let mut clock = FrameClock::new(Instant::now().as_micros());
+    loop {
+        let uniforms = clock.next_frame(Instant::now().as_micros(), WIDTH, HEIGHT, InputState::default());
+        // Send one frame while rendering the next
+        let render = async {
+            render_rows(&Gradient, &uniforms, PIXEL_FORMAT, back, WIDTH as usize, HEIGHT as usize, 0..HEIGHT as usize);
+        };
+        if join(display.swap_buffers(front), render).await.0.is_err() {
+            // Re-initialize the panel and carry on with the next frame
+            let _ = display.init(&mut Delay).await;
+        }
+        core::mem::swap(&mut front, &mut back);
+    }
+}
@@ -0,0 +1,207 @@
+//! RP2350 DMA backend for the display driver
+//!
+//! Whole frames go out on a single channel; streamed strips on a pair of chained channels.
+
+use fragments::dma::{DmaChannel, DmaTransfer};
+#[cfg(feature = "streaming")]
+use rp235x_hal::dma::double_buffer::{self, ReadNext};
+#[cfg(not(feature = "streaming"))]
+use rp235x_hal::dma::single_buffer;
+use rp235x_hal::dma::SingleChannel;
+#[cfg(feature = "streaming")]
+use rp235x_hal::dma::EndlessWriteTarget;
+use rp235x_hal::dma::{ReadTarget, WriteTarget};
+#[cfg(feature = "streaming")]
+use rp235x_hal::pac;
+
+#[cfg(not(feature = "streaming"))]
+/// DMA channel of the RP2350 that streams frame buffers to an SPI peripheral
+pub struct HalDma<CH: SingleChannel>(pub CH);
+
+#[cfg(not(feature = "streaming"))]
+/// Frame transfer running on an RP2350 DMA channel
+pub struct HalTransfer<CH: SingleChannel, SPI: WriteTarget<TransmittedWord = u8>>(
+    single_buffer::Transfer<CH, Prefix, SPI>,
+);
+
+/// The first `len` bytes of a frame buffer, so partial updates can reuse the full-size buffers
+pub struct Prefix {
+    buffer: &'static mut [u8],
+    len: usize,
+}
+
+/// Safety: the buffer is borrowed for `'static` and only handed back once the transfer is done,
+/// and `len` is clamped to its size.
+unsafe impl ReadTarget for Prefix {
+    type ReceivedWord = u8;
+
+    fn rx_treq() -> Option<u8> {
+        None
+    }
+
+    fn rx_address_count(&self) -> (u32, u32) {
+        (self.buffer.as_ptr() as u32, self.len.min(self.buffer.len()) as u32)
+    }
+
+    fn rx_increment(&self) -> bool {
+        true
+    }
+}
+
+#[cfg(not(feature = "streaming"))]
+impl<CH: SingleChannel, SPI: WriteTarget<TransmittedWord = u8>> DmaChannel<SPI> for HalDma<CH> {
+    type Transfer = HalTransfer<CH, SPI>;
+
+    fn start(self, buffer: &'static mut [u8], len: usize, spi: SPI) -> Self::Transfer {
+        HalTransfer(single_buffer::Config::new(self.0, Prefix { buffer, len }, spi).start())
+    }
+
+    fn ack_irq(&mut self) -> bool {
+        self.0.check_irq0()
+    }
+}
+
+#[cfg(not(feature = "streaming"))]
+impl<CH: SingleChannel, SPI: WriteTarget<TransmittedWord = u8>> DmaTransfer<SPI> for HalTransfer<CH, SPI> {
+    type Channel = HalDma<CH>;
+
+    fn is_done(&self) -> bool {
+        self.0.is_done()
+    }
+
+    fn ack_irq(&mut self) -> bool {
+        self.0.check_irq0()
+    }
+
+    fn wait(self) -> (Self::Channel, &'static mut [u8], SPI) {
+        let (ch, prefix, spi) = self.0.wait();
+        (HalDma(ch), prefix.buffer, spi)
+    }
+}
+
+#[cfg(feature = "streaming")]
+/// Pair of RP2350 DMA channels that stream strips back to back, each channel chained to the
+/// other so the next strip starts in hardware as soon as the running one ends
+pub struct HalChainedDma<CH1: SingleChannel, CH2: SingleChannel>(pub CH1, pub CH2);
+
+#[cfg(feature = "streaming")]
+/// Strip transfer running on a pair of chained channels
+pub struct HalChainedTransfer<CH1: SingleChannel, CH2: SingleChannel, SPI: WriteTarget<TransmittedWord = u8>> {
+    /// Only `None` while moving between states
+    chain: Option<Chain<CH1, CH2, SPI>>,
+    /// Channel running the current strip, then the one that follows it
+    ids: (u8, u8),
+}
+
+#[cfg(feature = "streaming")]
+enum Chain<CH1: SingleChannel, CH2: SingleChannel, SPI: WriteTarget<TransmittedWord = u8>> {
+    /// One strip, with nothing behind it
+    Running(double_buffer::Transfer<CH1, CH2, Prefix, SPI, ()>),
+    /// The other channel is set up with the next strip and triggered by the running one
+    Chained(double_buffer::Transfer<CH1, CH2, Prefix, SPI, ReadNext<Prefix>>),
+}
+
+#[cfg(feature = "streaming")]
+fn dma() -> &'static pac::dma::RegisterBlock {
+    // SAFETY: only used for registers of channels owned by the caller
+    unsafe { &*pac::DMA::ptr() }
+}
+
+#[cfg(feature = "streaming")]
+fn channel_busy(id: u8) -> bool {
+    dma().ch(id as usize).ch_ctrl_trig().read().busy().bit_is_set()
+}
+
+#[cfg(feature = "streaming")]
+impl<CH1, CH2, SPI> DmaChannel<SPI> for HalChainedDma<CH1, CH2>
+where
+    CH1: SingleChannel,
+    CH2: SingleChannel,
+    SPI: WriteTarget<TransmittedWord = u8> + EndlessWriteTarget,
+{
+    type Transfer = HalChainedTransfer<CH1, CH2, SPI>;
+
+    fn start(self, buffer: &'static mut [u8], len: usize, spi: SPI) -> Self::Transfer {
+        let ids = (self.0.id(), self.1.id());
+        let transfer = double_buffer::Config::new((self.0, self.1), Prefix { buffer, len }, spi).start();
+        HalChainedTransfer { chain: Some(Chain::Running(transfer)), ids }
+    }
+
+    fn ack_irq(&mut self) -> bool {
+        self.0.check_irq0() | self.1.check_irq0()
+    }
+}
+
+#[cfg(feature = "streaming")]
+impl<CH1, CH2, SPI> DmaTransfer<SPI> for HalChainedTransfer<CH1, CH2, SPI>
+where
+    CH1: SingleChannel,
+    CH2: SingleChannel,
+    SPI: WriteTarget<TransmittedWord = u8> + EndlessWriteTarget,
+{
+    type Channel = HalChainedDma<CH1, CH2>;
+
+    fn is_done(&self) -> bool {
+        match &self.chain {
+            Some(Chain::Running(transfer)) => transfer.is_done(),
+            Some(Chain::Chained(transfer)) => transfer.is_done() && !channel_busy(self.ids.1),
+            None => unreachable!(),
+        }
+    }
+
+    fn ack_irq(&mut self) -> bool {
+        let channels = (1 << self.ids.0) | (1 << self.ids.1);
+        let raised = dma().ints0().read().bits() & channels;
+        // SAFETY: only clears the flags of the two channels owned by the transfer
+        dma().ints0().write(|w| unsafe { w.bits(raised) });
+        raised != 0
+    }
+
+    fn chain(&mut self, buffer: &'static mut [u8], len: usize) -> Result<(), &'static mut [u8]> {
+        let transfer = match self.chain.take() {
+            Some(Chain::Running(transfer)) if !transfer.is_done() => transfer,
+            // Nothing left to trigger the next strip
+            chain => {
+                self.chain = chain;
+                return Err(buffer);
+            }
+        };
+        let start = buffer.as_ptr() as u32;
+        let transfer = transfer.read_next(Prefix { buffer, len });
+        // A strip that ended before the chain was set up did not trigger the next one, which
+        // then still waits at its start
+        let next = dma().ch(self.ids.1 as usize);
+        if transfer.is_done() && !channel_busy(self.ids.1) && next.ch_read_addr().read().bits() == start {
+            // SAFETY: only triggers the channel owned by the transfer
+            dma().multi_chan_trigger().write(|w| unsafe { w.bits(1 << self.ids.1) });
+        }
+        self.chain = Some(Chain::Chained(transfer));
+        Ok(())
+    }
+
+    fn take_sent(&mut self) -> Option<&'static mut [u8]> {
+        match self.chain.take() {
+            Some(Chain::Chained(transfer)) if transfer.is_done() => {
+                let (sent, transfer) = transfer.wait();
+                self.chain = Some(Chain::Running(transfer));
+                self.ids = (self.ids.1, self.ids.0);
+                Some(sent.buffer)
+            }
+            chain => {
+                self.chain = chain;
+                None
+            }
+        }
+    }
+
+    fn wait(self) -> (Self::Channel, &'static mut [u8], SPI) {
+        let transfer = match self.chain {
+            Some(Chain::Running(transfer)) => transfer,
+            // The display takes the sent strip back first, see `take_sent`
+            Some(Chain::Chained(transfer)) => transfer.wait().1,
+            None => unreachable!(),
+        };
+        let (ch1, ch2, prefix, spi) = transfer.wait();
+        (HalChainedDma(ch1, ch2), prefix.buffer, spi)
+    }
+}
@@ -0,0 +1,696 @@
+#![no_std]
+#![no_main]
+
+mod dma;
+
+use core::cell::RefCell;
+use core::panic::PanicInfo;
+
+use cortex_m::peripheral::syst::SystClkSource;
+use cortex_m::peripheral::NVIC;
+use cortex_m_rt::exception;
+use critical_section::{CriticalSection, Mutex};
+use embedded_hal::delay::DelayNs;
+use embedded_hal::digital::{InputPin, StatefulOutputPin};
+use rp235x_hal::gpio::{FunctionSioOutput, FunctionSpi, Pin, PinState, PullDown};
+use rp235x_hal::gpio::bank0::{Gpio8, Gpio9, Gpio10, Gpio11, Gpio12};
+use rp235x_hal::{self as hal, entry};
+use rp235x_hal::pac;
+use rp235x_hal::pac::interrupt;
+use rp235x_hal::dma::DMAExt;
+#[cfg(not(feature = "streaming"))]
+use rp235x_hal::dma::SingleChannel;
+use rp235x_hal::clocks::{Clock, ClocksManager, ClockSource, InitError};
+use rp235x_hal::pll::{PLLConfig, common_configs::{PLL_USB_48MHZ}, setup_pll_blocking};
+use rp235x_hal::sio::CoreId;
+use rp235x_hal::Sio;
+use rp235x_hal::singleton;
+use rp235x_hal::watchdog::Watchdog;
+use rp235x_hal::xosc::setup_xosc_blocking;
+use rp235x_hal::multicore::{Multicore, Stack};
+
+
+use fugit::{RateExtU32, HertzU32};
+
+#[cfg(all(feature = "pipelining", any(feature = "streaming", feature = "triple-buffering", feature = "adaptive-split")))]
+compile_error!("`pipelining` rotates three whole frames of its own and cannot be combined with other rendering modes");
+
+#[cfg(not(feature = "streaming"))]
+use fragments::display::DisplayError;
+use fragments::display::{Orientation, Rotation, WaveshareST7789Display};
+#[cfg(feature = "pipelining")]
+use fragments::color::Color;
+use fragments::color::PixelFormat;
+use fragments::framebuffer::{HEIGHT, WIDTH};
+use fragments::handoff::{Handoff, Strip};
+use fragments::panel::St7789;
+use fragments::panel_config::PanelConfig;
+#[cfg(feature = "pipelining")]
+use fragments::pipeline::Pipeline;
+use fragments::power::{IdlePolicy, PowerAction};
+use fragments::schedule::{CoreStats, LoadMonitor};
+#[cfg(feature = "pipelining")]
+use fragments::schedule::CORES;
+#[cfg(feature = "adaptive-split")]
+use fragments::schedule::AdaptiveSplit;
+use fragments::shader::render_strip;
+use fragments::shaders;
+use fragments::supervisor::{FaultLog, Heartbeat, Recovery, RestartPolicy, StallDetector};
+use fragments::timing::FrameTimer;
+use fragments::uniforms::{FrameClock, InputState, Uniforms};
+
+#[cfg(not(feature = "streaming"))]
+use dma::HalDma;
+#[cfg(feature = "streaming")]
+use dma::HalChainedDma;
+
+/// Tell the Boot ROM about our application
+#[unsafe(link_section = ".start_block")]
+#[used]
+pub static IMAGE_DEF: hal::block::ImageDef = hal::block::ImageDef::secure_exe();
+
+
+
+/// Pixel format sent to the display; RGB565 halves the SPI traffic compared to RGB666
+const PIXEL_FORMAT: PixelFormat = PixelFormat::Rgb565;
+
+/// Panel tuning; lower the refresh rate towards the render rate to save power
+const PANEL: St7789 = St7789::LCD_240X320.with_config(PanelConfig::new().with_frame_rate(60));
+
+/// Orientation of the rendered image; quarter turns render in landscape
+const ORIENTATION: Orientation = Orientation::new(Rotation::Deg0, false);
+const FRAME_WIDTH: u16 = ORIENTATION.size(WIDTH, HEIGHT).0;
+const FRAME_HEIGHT: u16 = ORIENTATION.size(WIDTH, HEIGHT).1;
+
+/// Rows held by each buffer: a whole frame, or with the `streaming` feature a strip that is
+/// sent while the cores render the next one
+#[cfg(not(feature = "streaming"))]
+const BUFFER_ROWS: usize = FRAME_HEIGHT as usize;
+#[cfg(feature = "streaming")]
+const BUFFER_ROWS: usize = 16;
+const BUFFER_SIZE: usize = PIXEL_FORMAT.bytes_for(FRAME_WIDTH as usize * BUFFER_ROWS);
+
+/// Backlight brightness out of 255, faded in at boot; lower it for dark rooms or battery builds
+const BRIGHTNESS: u8 = 255;
+const FADE_IN_US: u32 = 500_000;
+
+/// Put the display to sleep after this long without a button press, and run the system clock
+/// at 1/IDLE_CLOCK_DIVIDER while asleep, polling the buttons every IDLE_POLL_MS
+const IDLE_TIMEOUT_US: u64 = 5 * 60 * 1_000_000;
+const IDLE_CLOCK_DIVIDER: u32 = 10;
+const IDLE_POLL_MS: u32 = 50;
+
+type Timer = hal::Timer<hal::timer::CopyableTimer0>;
+type LcdSpi = hal::spi::Spi<hal::spi::Enabled, pac::SPI1, (Pin<Gpio11, FunctionSpi, PullDown>, Pin<Gpio10, FunctionSpi, PullDown>), 8>;
+type LcdPin<I> = Pin<I, FunctionSioOutput, PullDown>;
+type LcdBacklight = hal::pwm::Channel<hal::pwm::Slice<hal::pwm::Pwm6, hal::pwm::FreeRunning>, hal::pwm::B>;
+#[cfg(not(feature = "streaming"))]
+type LcdDma = HalDma<hal::dma::Channel<hal::dma::CH0>>;
+#[cfg(feature = "streaming")]
+type LcdDma = HalChainedDma<hal::dma::Channel<hal::dma::CH0>, hal::dma::Channel<hal::dma::CH1>>;
+type Display = WaveshareST7789Display<LcdSpi, LcdPin<Gpio9>, LcdPin<Gpio8>, LcdPin<Gpio12>, LcdDma, LcdBacklight>;
+
+/// The display driver with the delay it needs, shared with the DMA interrupt handler
+struct Lcd {
+    display: Display,
+    delay: Timer,
+}
+
+static LCD: Mutex<RefCell<Option<Lcd>>> = Mutex::new(RefCell::new(None));
+
+/// Run `f` in a critical section, which only core 0 may enter
+///
+/// Core 0 stops a failed core 1 from within a critical section (`stop_failed_core1`). Core 1
+/// must never hold the spinlock that these take, or stopping it would leave the lock taken
+/// for good; debug builds check which core is asking.
+fn core0_critical_section<R>(f: impl FnOnce(CriticalSection<'_>) -> R) -> R {
+    debug_assert_eq!(Sio::core(), CoreId::Core0, "core 1 entered a critical section");
+    critical_section::with(f)
+}
+
+/// Run `f` on the shared display with interrupts masked
+fn with_lcd<R>(f: impl FnOnce(&mut Lcd) -> R) -> R {
+    core0_critical_section(|cs| f(LCD.borrow_ref_mut(cs).as_mut().unwrap()))
+}
+
+/// Run `f` on the display outside the critical section, for calls that wait on the panel:
+/// `init`, and the 120 ms in `sleep` and `wake`
+///
+/// The display's interrupt is masked meanwhile, as its handler cannot get at the display.
+fn with_lcd_unlocked<R>(f: impl FnOnce(&mut Lcd) -> R) -> R {
+    let irq_enabled = NVIC::is_enabled(pac::Interrupt::DMA_IRQ_0);
+    NVIC::mask(pac::Interrupt::DMA_IRQ_0);
+    let mut lcd = core0_critical_section(|cs| LCD.borrow(cs).take()).unwrap();
+    let result = f(&mut lcd);
+    core0_critical_section(|cs| LCD.borrow(cs).replace(Some(lcd)));
+    if irq_enabled {
+        // SAFETY: the handler only reaches the display through the critical section
+        unsafe {
+            NVIC::unmask(pac::Interrupt::DMA_IRQ_0);
+        }
+    }
+    result
+}
+
+/// Queue a rendered frame and get a free buffer back, sleeping while none is free
+#[cfg(not(feature = "streaming"))]
+fn queue_frame(buffer: &mut &'static mut [u8]) -> Result<(), DisplayError> {
+    loop {
+        let result = with_lcd(|lcd| lcd.display.queue_frame(&mut lcd.delay, buffer));
+        if result != Err(DisplayError::NoFreeBuffer) {
+            return result;
+        }
+        // Sleep with interrupts masked, so a transfer ending after the check still ends the
+        // sleep, but outside the critical section: its spinlock stays free while waiting
+        cortex_m::interrupt::free(|_| {
+            if !with_lcd(|lcd| lcd.display.buffer_free()) {
+                cortex_m::asm::wfi();
+            }
+        });
+    }
+}
+
+/// Free the transmitted buffer and start the next queued frame as soon as a transfer ends
+#[interrupt]
+fn DMA_IRQ_0() {
+    core0_critical_section(|cs| {
+        if let Some(lcd) = LCD.borrow_ref_mut(cs).as_mut() {
+            lcd.display.acknowledge_interrupt();
+            // A failed kickoff leaves the frame queued until the next `queue_frame` supersedes it
+            let _ = lcd.display.on_transfer_complete(&mut lcd.delay, true);
+        }
+    });
+}
+
+/// Shader rendered by both cores
+static SHADER: shaders::Gradient = shaders::Gradient;
+
+
+static CORE1_STACK: Stack<4096> = Stack::new();
+
+/// Rows of each buffer rendered by core 1 with the frame's uniforms, or whole frames with the
+/// `pipelining` feature; both cores sleep in `wfe` while waiting for each other
+// SAFETY: `stop_failed_core1` only gives up on core 1 once it is held in reset
+static HANDOFF: Handoff<Uniforms> =
+    unsafe { Handoff::with_recovery(cortex_m::asm::wfe, cortex_m::asm::sev, stop_failed_core1) };
+
+/// Rows rendered by each core, and the time it took
+static CORE_STATS: CoreStats = CoreStats::new();
+
+/// Bytes per frame row
+const STRIDE: usize = PIXEL_FORMAT.bytes_for(FRAME_WIDTH as usize);
+
+/// Rows per band of work; both cores take bands until the buffer is rendered, so the load
+/// evens out however the cost of the shader is spread over the frame. With the
+/// `adaptive-split` feature, each core renders one range of rows instead.
+#[cfg(not(any(feature = "adaptive-split", feature = "pipelining")))]
+const BAND_ROWS: usize = 8;
+// `Renderer` keeps track of the bands rendered by core 0 in a `u64`
+#[cfg(not(any(feature = "adaptive-split", feature = "pipelining")))]
+const _: () = assert!(BUFFER_ROWS.div_ceil(BAND_ROWS) <= 64);
+
+/// Period over which the per-core utilization is summed up
+const STATS_WINDOW_US: u64 = 1_000_000;
+
+/// Time core 0 waits for core 1 to finish a row before it stops core 1
+///
+/// Core 1 beats after every row, so this only has to cover the slowest row rather than the
+/// largest band, which is a whole frame with `adaptive-split` or `pipelining`. It allows over
+/// 1 ms per pixel of a 240-pixel row, even at 1/IDLE_CLOCK_DIVIDER of the system clock.
+const CORE1_TIMEOUT_US: u64 = 250_000;
+/// Restarts of a failed core 1 before core 0 renders on its own
+const CORE1_MAX_RESTARTS: u32 = 3;
+
+/// Panic of either core, kept across the reset that follows a panic of core 0 as this section
+/// is not initialized at boot
+#[unsafe(link_section = ".uninit.FAULTS")]
+static FAULTS: FaultLog = FaultLog::new();
+
+/// One beat for every row core 1 renders
+static HEARTBEAT: Heartbeat = Heartbeat::new();
+
+/// Whether core 1 is held in reset after it failed, with what is needed to tell
+struct Core1Watch {
+    timer: Timer,
+    stall: StallDetector,
+    stopped: bool,
+}
+
+static CORE1_WATCH: Mutex<RefCell<Option<Core1Watch>>> = Mutex::new(RefCell::new(None));
+
+fn with_core1_watch<R>(f: impl FnOnce(&mut Core1Watch) -> R) -> R {
+    core0_critical_section(|cs| f(CORE1_WATCH.borrow_ref_mut(cs).as_mut().unwrap()))
+}
+
+fn core1_stopped() -> bool {
+    with_core1_watch(|watch| watch.stopped)
+}
+
+/// Recovery hook of `HANDOFF`, called by core 0 while it waits for core 1: holds core 1 in
+/// reset once it has panicked or made no progress for `CORE1_TIMEOUT_US`
+fn stop_failed_core1() -> bool {
+    with_core1_watch(|watch| {
+        if !watch.stopped {
+            let panicked = FAULTS.get().is_some_and(|fault| fault.core == 1);
+            if panicked || watch.stall.stalled(watch.timer.get_counter().ticks(), &HEARTBEAT) {
+                // SAFETY: only the power state of core 1 is touched, which `spawn` sets up again
+                let psm = unsafe { &*pac::PSM::ptr() };
+                psm.frce_off().modify(|_, w| w.proc1().set_bit());
+                while !psm.frce_off().read().proc1().bit_is_set() {}
+                watch.stopped = true;
+            }
+        }
+        watch.stopped
+    })
+}
+
+/// Record the panic, then wait for core 0 to stop core 1, or reset the chip for a panic of
+/// core 0
+#[panic_handler]
+fn panic(info: &PanicInfo) -> ! {
+    let core = Sio::core() as u8;
+    FAULTS.record(core, format_args!("{info}"));
+    if core == 0 {
+        cortex_m::peripheral::SCB::sys_reset();
+    }
+    loop {
+        cortex_m::asm::wfe();
+    }
+}
+
+/// Only there to wake core 0 from `wfe` now and then, so that it checks on core 1
+#[exception]
+fn SysTick() {}
+
+fn core1_task(timer: Timer) -> ! {
+    loop {
+        HANDOFF.serve(|band, uniforms| render_band(band, uniforms, 1, &timer));
+    }
+}
+
+/// Render all rows of `band` on `core` and count them in `CORE_STATS`
+///
+/// Core 1 beats `HEARTBEAT` after every row, so that a slow shader or clock does not pass for
+/// a stall.
+fn render_band(band: Strip<'_>, uniforms: &Uniforms, core: usize, timer: &Timer) {
+    let start = timer.get_counter().ticks();
+    let rows = band.rows(STRIDE);
+    for row in rows.clone() {
+        render_strip(
+            &SHADER,
+            uniforms,
+            PIXEL_FORMAT,
+            band.bytes,
+            FRAME_WIDTH as usize,
+            FRAME_HEIGHT as usize,
+            band.first_row,
+            row..row + 1,
+        );
+        if core == 1 {
+            HEARTBEAT.beat();
+        }
+    }
+    CORE_STATS.record(core, rows.len(), timer.get_counter().ticks() - start);
+}
+
+/// Splits the rows of each buffer between both cores
+#[cfg(not(feature = "pipelining"))]
+struct Renderer {
+    timer: Timer,
+    #[cfg(feature = "adaptive-split")]
+    split: AdaptiveSplit,
+}
+
+#[cfg(not(feature = "pipelining"))]
+impl Renderer {
+    /// Render the rows of `buffer` starting at `first_row` on both cores, or on core 0 alone
+    /// once core 1 is off for good
+    fn render(&mut self, buffer: &mut [u8], uniforms: &Uniforms, first_row: usize, single_core: bool) {
+        let rows = BUFFER_ROWS.min(FRAME_HEIGHT as usize - first_row);
+        let strip = Strip::new(&mut buffer[..rows * STRIDE], first_row);
+        let timer = &self.timer;
+        if single_core {
+            render_band(strip, uniforms, 0, timer);
+            return;
+        }
+
+        #[cfg(not(feature = "adaptive-split"))]
+        {
+            // Bands rendered by core 0, one bit each
+            let mut own_bands = 0u64;
+            HANDOFF.share(strip, BAND_ROWS, STRIDE, *uniforms, |band, uniforms| {
+                own_bands |= 1 << ((band.first_row - first_row) / BAND_ROWS);
+                render_band(band, uniforms, 0, timer);
+            });
+            // Core 1 failed, maybe halfway through a band; render all bands core 0 did not
+            if core1_stopped() {
+                for band in (0..rows.div_ceil(BAND_ROWS)).filter(|band| own_bands & 1 << band == 0) {
+                    let band_rows = band * BAND_ROWS..((band + 1) * BAND_ROWS).min(rows);
+                    let strip = Strip::new(&mut buffer[band_rows.start * STRIDE..band_rows.end * STRIDE], first_row + band_rows.start);
+                    render_band(strip, uniforms, 0, timer);
+                }
+            }
+        }
+
+        // Core 0 renders the upper rows, core 1 the rest, and the boundary moves towards the
+        // point where both finish together
+        #[cfg(feature = "adaptive-split")]
+        {
+            let own_rows = self.split.core0_rows(rows);
+            let (own, lent) = strip.split_at_row(own_rows, STRIDE);
+            let parts = [own.rows(STRIDE).len(), lent.rows(STRIDE).len()];
+            HANDOFF.run(lent, *uniforms, || render_band(own, uniforms, 0, timer));
+            if core1_stopped() {
+                // Core 1 failed halfway through its rows, and recorded no time for them
+                let strip = Strip::new(&mut buffer[own_rows * STRIDE..rows * STRIDE], first_row + own_rows);
+                render_band(strip, uniforms, 0, timer);
+            } else {
+                let busy_us = [0, 1].map(|core| CORE_STATS.last_busy_us(core) as u64);
+                self.split.update(parts, busy_us);
+            }
+        }
+    }
+}
+
+/// Rows of each core's load bar
+#[cfg(feature = "pipelining")]
+const LOAD_BAR_ROWS: usize = 3;
+
+/// Finishing stage of the pipeline, run on core 0 while core 1 shades the next frame
+///
+/// Draws the share of the last second each core was busy as a bar along the top of the frame.
+/// Heavier post-processing passes go here as well.
+#[cfg(feature = "pipelining")]
+fn draw_load_bars(buffer: &mut [u8], core_load: &LoadMonitor) {
+    for core in 0..CORES {
+        // Whole pairs of pixels, which RGB444 needs
+        let len = (FRAME_WIDTH as usize * core_load.utilization_percent(core) as usize / 100) & !1;
+        let color = if core == 0 { Color::new(1.0, 0.5, 0.0) } else { Color::new(0.0, 0.8, 1.0) };
+        for row in core * LOAD_BAR_ROWS..(core + 1) * LOAD_BAR_ROWS {
+            let start = row * STRIDE;
+            PIXEL_FORMAT.encode(&mut buffer[start..start + PIXEL_FORMAT.bytes_for(len)], core::iter::repeat(color));
+        }
+    }
+}
+
+
+#[entry]
+fn main() -> ! {
+    let mut peripherals = pac::Peripherals::take().unwrap();
+    let mut cortex = cortex_m::Peripherals::take().unwrap();
+    let mut watchdog = Watchdog::new(peripherals.WATCHDOG);
+
+    // External high-speed crystal on the Pico 2 board is 12 MHz
+    const XOSC_CRYSTAL_FREQ: u32 = 12_000_000; 
+
+    // Enable the xosc
+    let xosc = setup_xosc_blocking(peripherals.XOSC, XOSC_CRYSTAL_FREQ.Hz()).map_err(InitError::XoscErr).unwrap();
+
+    // Start tick in watchdog
+    watchdog.enable_tick_generation((XOSC_CRYSTAL_FREQ / 1_000_000) as u16);
+
+    let mut clocks = ClocksManager::new(peripherals.CLOCKS);
+
+    // Configure PLLs
+    const PLL_SYS_250MHZ: PLLConfig = PLLConfig {
+        vco_freq: HertzU32::MHz(1500),
+        refdiv: 1,
+        post_div1: 3,
+        post_div2: 2,
+    };
+    let pll_sys = setup_pll_blocking(peripherals.PLL_SYS, xosc.operating_frequency().into(), PLL_SYS_250MHZ, &mut clocks, &mut peripherals.RESETS).map_err(InitError::PllError).unwrap();
+    let pll_usb = setup_pll_blocking(peripherals.PLL_USB, xosc.operating_frequency().into(), PLL_USB_48MHZ, &mut clocks, &mut peripherals.RESETS).map_err(InitError::PllError).unwrap();
+
+    // Configure clocks
+    // CLK_REF = XOSC (12MHz) / 1 = 12MHz
+    clocks.reference_clock.configure_clock(&xosc, xosc.get_freq()).map_err(InitError::ClockError).unwrap();
+
+    // CLK SYS = PLL SYS (250MHz) / 1 = 250MHz
+    clocks.system_clock.configure_clock(&pll_sys, pll_sys.get_freq()).map_err(InitError::ClockError).unwrap();
+
+    // CLK USB = PLL USB (48MHz) / 1 = 48MHz
+    clocks.usb_clock.configure_clock(&pll_usb, pll_usb.get_freq()).map_err(InitError::ClockError).unwrap();
+
+    // CLK ADC = PLL USB (48MHZ) / 1 = 48MHz
+    clocks.adc_clock.configure_clock(&pll_usb, pll_usb.get_freq()).map_err(InitError::ClockError).unwrap();
+
+    // CLK HSTX = PLL SYS (250MHz) / 1 = 250MHz
+    clocks.hstx_clock.configure_clock(&pll_sys, pll_sys.get_freq()).map_err(InitError::ClockError).unwrap();
+
+    // CLK PERI = clk_sys. Used as reference clock for Peripherals. No dividers so just select and enable
+    clocks.peripheral_clock.configure_clock(&clocks.system_clock, clocks.system_clock.freq()).map_err(InitError::ClockError).unwrap();
+
+    let timer = hal::Timer::new_timer0(peripherals.TIMER0, &mut peripherals.RESETS, &clocks);
+    let mut delay_for_app = timer.clone();
+    let mut idle_delay = timer.clone();
+    core0_critical_section(|cs| {
+        let watch = Core1Watch { timer, stall: StallDetector::new(CORE1_TIMEOUT_US), stopped: false };
+        CORE1_WATCH.borrow(cs).replace(Some(watch));
+    });
+    // Fault that made core 0 reset the chip, if any; inspect it with a debugger
+    let mut last_fault = FAULTS.take();
+
+    // Wake core 0 every 10 ms, also while it waits for core 1
+    cortex.SYST.set_clock_source(SystClkSource::Core);
+    cortex.SYST.set_reload(clocks.system_clock.freq().to_Hz() / 100 - 1);
+    cortex.SYST.clear_current();
+    cortex.SYST.enable_interrupt();
+    cortex.SYST.enable_counter();
+
+    let mut sio = Sio::new(peripherals.SIO);
+    let pins = hal::gpio::Pins::new(
+        peripherals.IO_BANK0,
+        peripherals.PADS_BANK0,
+        sio.gpio_bank0,
+        &mut peripherals.RESETS,
+    );
+    
+    let lcd_clk = pins.gpio10.into_function::<hal::gpio::FunctionSpi>();
+    let lcd_din = pins.gpio11.into_function::<hal::gpio::FunctionSpi>();
+    let lcd_cs = pins.gpio9.into_push_pull_output_in_state(PinState::High);
+    let lcd_dc = pins.gpio8.into_push_pull_output_in_state(PinState::Low);
+    let lcd_rst = pins.gpio12.into_push_pull_output_in_state(PinState::High);
+
+    // Backlight on PWM slice 6, channel B; dark until faded in after init
+    let pwm_slices = hal::pwm::Slices::new(peripherals.PWM, &mut peripherals.RESETS);
+    let mut backlight_pwm = pwm_slices.pwm6;
+    backlight_pwm.set_ph_correct();
+    backlight_pwm.enable();
+    let mut lcd_bl = backlight_pwm.channel_b;
+    lcd_bl.output_to(pins.gpio13);
+
+    let mut led_pin = pins.gpio25.into_push_pull_output_in_state(PinState::High);
+    let mut key0 = pins.gpio15.into_pull_up_input();
+    let mut key1 = pins.gpio17.into_pull_up_input();
+    let mut key2 = pins.gpio2.into_pull_up_input();
+    let mut key3 = pins.gpio3.into_pull_up_input();
+
+    let mut mc = Multicore::new(&mut peripherals.PSM, &mut peripherals.PPB, &mut sio.fifo);
+    let cores = mc.cores();
+    let core1 = &mut cores[1];
+    let _test = core1.spawn(CORE1_STACK.take().unwrap(), move || core1_task(timer));
+
+    // Configure SPI
+    let spi = hal::spi::Spi::<_, _, _, 8>::new(peripherals.SPI1, (lcd_din, lcd_clk));
+    
+    // ST7789 can support up to 62.5 MHz, but start conservatively at 16 MHz
+    let spi = spi.init(
+        &mut peripherals.RESETS,
+        clocks.peripheral_clock.freq(),
+        250u32.MHz(),
+        embedded_hal::spi::MODE_0,
+    );
+    
+    // Initialize DMA
+    let dma = peripherals.DMA.split(&mut peripherals.RESETS);
+    #[cfg(not(feature = "streaming"))]
+    let lcd_dma = {
+        let mut lcd_dma = dma.ch0;
+        lcd_dma.enable_irq0();
+        HalDma(lcd_dma)
+    };
+    // Strips are sent by two channels triggering each other
+    #[cfg(feature = "streaming")]
+    let lcd_dma = HalChainedDma(dma.ch0, dma.ch1);
+    
+    // Allocate two buffers for double buffering, whole frames or strips
+    let buffer_a: &'static mut [u8] = singleton!(: [u8; BUFFER_SIZE] = [0u8; BUFFER_SIZE]).unwrap();
+    let buffer_b: &'static mut [u8] = singleton!(: [u8; BUFFER_SIZE] = [0u8; BUFFER_SIZE]).unwrap();
+
+    let mut display = WaveshareST7789Display::new(PANEL, spi, lcd_cs, lcd_dc, lcd_rst, lcd_dma, buffer_a)
+        .with_pixel_format(PIXEL_FORMAT)
+        .with_orientation(ORIENTATION)
+        .with_backlight(lcd_bl);
+
+    // A third buffer lets the cores render while one frame or strip is sent and the next one
+    // waits, chained behind it when streaming
+    #[cfg(any(feature = "triple-buffering", feature = "streaming"))]
+    {
+        let buffer_c: &'static mut [u8] = singleton!(: [u8; BUFFER_SIZE] = [0u8; BUFFER_SIZE]).unwrap();
+        display = display.with_spare_buffer(buffer_c);
+    }
+
+    // Or keep it for the pipeline, which shades into one buffer while finishing another
+    #[cfg(feature = "pipelining")]
+    let mut pipeline = {
+        let buffer_c: &'static mut [u8] = singleton!(: [u8; BUFFER_SIZE] = [0u8; BUFFER_SIZE]).unwrap();
+        Pipeline::new(buffer_b, buffer_c)
+    };
+    #[cfg(not(feature = "pipelining"))]
+    let mut buffer = buffer_b;
+
+    // Initialize the display, retrying until the panel responds
+    while display.init(&mut delay_for_app).is_err() {}
+    display.fade_brightness(BRIGHTNESS, FADE_IN_US, timer.get_counter().ticks());
+    core0_critical_section(|cs| LCD.borrow(cs).replace(Some(Lcd { display, delay: delay_for_app })));
+
+    // Whole frames are handed over by the DMA completion interrupt; streamed strips follow
+    // each other too closely to be worth an interrupt each
+    #[cfg(not(feature = "streaming"))]
+    unsafe {
+        cortex_m::peripheral::NVIC::unmask(pac::Interrupt::DMA_IRQ_0);
+    }
+
+    let mut clock = FrameClock::new(timer.get_counter().ticks());
+    // Frame time, and the part of it spent rendering rather than waiting for the display;
+    // inspect them with a debugger
+    let mut frame_timer = FrameTimer::new();
+    let mut render_timer = FrameTimer::new();
+    #[cfg(not(feature = "pipelining"))]
+    let mut renderer = Renderer {
+        timer,
+        #[cfg(feature = "adaptive-split")]
+        split: AdaptiveSplit::new(),
+    };
+    // Share of each second that each core spent rendering
+    let mut core_load = LoadMonitor::new(STATS_WINDOW_US, timer.get_counter().ticks());
+    let mut idle = IdlePolicy::new(IDLE_TIMEOUT_US, timer.get_counter().ticks());
+    let mut core1_restarts = RestartPolicy::new(CORE1_MAX_RESTARTS);
+    let mut single_core = false;
+    
+    // Main rendering loop
+    loop {
+        // Sample time and input once so that both cores render the same instant
+        let input = InputState::from_pressed([
+            key0.is_low().unwrap_or(false),
+            key1.is_low().unwrap_or(false),
+            key2.is_low().unwrap_or(false),
+            key3.is_low().unwrap_or(false),
+        ]);
+        match idle.update(timer.get_counter().ticks(), input) {
+            Some(PowerAction::Sleep) => {
+                let _ = with_lcd_unlocked(|lcd| lcd.display.sleep(&mut lcd.delay));
+                // The frame waiting to be finished would be stale by the time the display wakes
+                #[cfg(feature = "pipelining")]
+                pipeline.discard();
+                // Nothing is rendered while idle, so let both cores crawl
+                let _ = clocks.system_clock.configure_clock(&pll_sys, pll_sys.get_freq() / IDLE_CLOCK_DIVIDER);
+            }
+            Some(PowerAction::Wake) => {
+                // Back to full speed before SPI traffic resumes; a failed wake makes the next
+                // frame fail with `Asleep`, which re-initializes the panel
+                let _ = clocks.system_clock.configure_clock(&pll_sys, pll_sys.get_freq());
+                let _ = with_lcd_unlocked(|lcd| lcd.display.wake(&mut lcd.delay));
+            }
+            None => {}
+        }
+        if idle.is_idle() {
+            idle_delay.delay_ms(IDLE_POLL_MS);
+            continue;
+        }
+        let frame_start = timer.get_counter().ticks();
+        frame_timer.tick(frame_start);
+        // The hardware PWM cannot fail
+        let _ = with_lcd(|lcd| lcd.display.update_backlight(frame_start));
+        core_load.update(frame_start, &CORE_STATS);
+        let uniforms = clock.next_frame(frame_start, FRAME_WIDTH, FRAME_HEIGHT, input);
+        with_core1_watch(|watch| watch.stall.arm(frame_start, &HEARTBEAT));
+
+        // Fill the buffer we have
+        #[cfg(not(any(feature = "streaming", feature = "pipelining")))]
+        let result = {
+            renderer.render(buffer, &uniforms, 0, single_core);
+            render_timer.record(timer.get_counter().ticks() - frame_start);
+            queue_frame(&mut buffer)
+        };
+
+        // Core 1 shades this frame while core 0 finishes and queues the previous one; the
+        // render time covers both stages
+        #[cfg(feature = "pipelining")]
+        let result = {
+            let finish = |shaded: &mut &'static mut [u8], _: &Uniforms| {
+                let finish_start = timer.get_counter().ticks();
+                draw_load_bars(shaded, &core_load);
+                CORE_STATS.record(0, 0, timer.get_counter().ticks() - finish_start);
+                queue_frame(shaded)
+            };
+            let result = if single_core {
+                pipeline.step_local(uniforms, |strip, uniforms| render_band(strip, uniforms, 0, &timer), finish)
+            } else {
+                pipeline.step(&HANDOFF, uniforms, finish)
+            };
+            render_timer.record(timer.get_counter().ticks() - frame_start);
+            result.unwrap_or(Ok(()))
+        };
+
+        // Render strip after strip while the previous one is transmitted
+        #[cfg(feature = "streaming")]
+        let result = with_lcd(|lcd| lcd.display.begin_frame(&mut lcd.delay)).and_then(|()| {
+            let mut render_us = 0;
+            for first_row in (0..FRAME_HEIGHT as usize).step_by(BUFFER_ROWS) {
+                let render_start = timer.get_counter().ticks();
+                renderer.render(buffer, &uniforms, first_row, single_core);
+                render_us += timer.get_counter().ticks() - render_start;
+                let rows = BUFFER_ROWS.min(FRAME_HEIGHT as usize - first_row);
+                with_lcd(|lcd| lcd.display.push_strip(&mut buffer, rows))?;
+            }
+            render_timer.record(render_us);
+            Ok(())
+        });
+
+        if result.is_err() {
+            // Re-initialize the panel; frames are submitted again once that succeeds
+            let _ = with_lcd_unlocked(|lcd| lcd.display.init(&mut lcd.delay));
+        }
+        
+        // Core 1 panicked or stopped making progress, and is held in reset
+        if !single_core && core1_stopped() {
+            // Core 1 may have been stopped in the middle of recording its panic
+            FAULTS.abandon_unfinished();
+            last_fault = FAULTS.take().or(last_fault);
+            // The frame core 1 was shading is incomplete
+            #[cfg(feature = "pipelining")]
+            pipeline.discard();
+            match core1_restarts.on_failure() {
+                Recovery::Restart => {
+                    // SAFETY: core 1 is held in reset, so nothing runs on its stack
+                    unsafe { CORE1_STACK.reset() };
+                    let restarted = core1.spawn(CORE1_STACK.take().unwrap(), move || core1_task(timer)).is_ok();
+                    with_core1_watch(|watch| watch.stopped = !restarted);
+                    // The split learnt from the failed core starts over
+                    #[cfg(feature = "adaptive-split")]
+                    {
+                        renderer.split = AdaptiveSplit::new();
+                    }
+                }
+                Recovery::SingleCore => single_core = true,
+            }
+        }
+
+        // Toggle LED to show activity, faster after a fault
+        if uniforms.frame % if last_fault.is_some() { 6 } else { 30 } == 0 {
+            let _ = led_pin.toggle();
+        }
+    }
+}
+
+/// Program metadata for `picotool info`
+#[unsafe(link_section = ".bi_entries")]
+#[used]
+pub static PICOTOOL_ENTRIES: [rp235x_hal::binary_info::EntryAddr; 5] = [
+    rp235x_hal::binary_info::rp_cargo_bin_name!(),
+    rp235x_hal::binary_info::rp_cargo_version!(),
+    rp235x_hal::binary_info::rp_program_description!(c"rusty-pico-fragments"),
+    rp235x_hal::binary_info::rp_cargo_homepage_url!(),
+    rp235x_hal::binary_info::rp_program_build_attribute!(),
+];
@@ -0,0 +1,114 @@
+//! Backlight brightness control
+//!
+//! Drives the backlight LED of a panel with a PWM channel. Brightness is given as a perceived
+//! level from 0 to 255 and mapped onto the duty cycle through a gamma curve, so that equal
+//! steps look equal and fades run evenly down to black.
+
+use embedded_hal::pwm::{Error as PwmError, SetDutyCycle};
+
+use crate::display::DisplayError;
+
+/// Duty cycle for a perceived brightness `level` out of 255, for a PWM counting to `max_duty`
+///
+/// Follows a 2.2 gamma curve, approximated as 0.8 x² + 0.2 x³.
+pub fn gamma_duty(level: u8, max_duty: u16) -> u16 {
+    let x = level as u64;
+    let scaled = 4 * x * x * 255 + x * x * x;
+    (max_duty as u64 * scaled / (5 * 255 * 255 * 255)) as u16
+}
+
+/// Stand-in for panels whose backlight is wired to a fixed supply
+///
+/// Accepts any duty cycle and does nothing.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct NoBacklight;
+
+impl embedded_hal::pwm::ErrorType for NoBacklight {
+    type Error = core::convert::Infallible;
+}
+
+impl SetDutyCycle for NoBacklight {
+    fn max_duty_cycle(&self) -> u16 {
+        u16::MAX
+    }
+
+    fn set_duty_cycle(&mut self, _duty: u16) -> Result<(), Self::Error> {
+        Ok(())
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct Fade {
+    from: u8,
+    to: u8,
+    start_us: u64,
+    duration_us: u64,
+}
+
+/// Backlight on a PWM channel with gamma-corrected brightness and timed fades
+///
+/// Fades are advanced by `update` with the time from a free-running microsecond counter, so
+/// they run alongside rendering without blocking.
+pub struct Backlight<PWM: SetDutyCycle> {
+    pwm: PWM,
+    level: u8,
+    fade: Option<Fade>,
+}
+
+impl<PWM: SetDutyCycle> Backlight<PWM> {
+    /// Take over `pwm`; the brightness counts as 0 until it is first set
+    pub fn new(pwm: PWM) -> Self {
+        Self { pwm, level: 0, fade: None }
+    }
+
+    /// Current perceived brightness, out of 255
+    pub fn brightness(&self) -> u8 {
+        self.level
+    }
+
+    /// Set the perceived brightness right away, stopping a fade in progress
+    pub fn set_brightness(&mut self, level: u8) -> Result<(), DisplayError> {
+        self.fade = None;
+        self.apply(level)
+    }
+
+    /// Start fading from the current brightness to `level` over `duration_us`, beginning at the
+    /// counter value `now_us`
+    pub fn fade_to(&mut self, level: u8, duration_us: u32, now_us: u64) {
+        self.fade = Some(Fade { from: self.level, to: level, start_us: now_us, duration_us: duration_us as u64 });
+    }
+
+    /// Whether a fade is still in progress
+    pub fn is_fading(&self) -> bool {
+        self.fade.is_some()
+    }
+
+    /// Advance a fade to the counter value `now_us`
+    ///
+    /// Call this regularly, e.g. once per frame; it does nothing without a fade.
+    pub fn update(&mut self, now_us: u64) -> Result<(), DisplayError> {
+        let Some(fade) = self.fade else {
+            return Ok(());
+        };
+        let elapsed_us = now_us.saturating_sub(fade.start_us);
+        if elapsed_us >= fade.duration_us {
+            self.fade = None;
+            return self.apply(fade.to);
+        }
+        let (from, to) = (fade.from as i64, fade.to as i64);
+        let level = from + (to - from) * elapsed_us as i64 / fade.duration_us as i64;
+        self.apply(level as u8)
+    }
+
+    /// Release the PWM channel
+    pub fn release(self) -> PWM {
+        self.pwm
+    }
+
+    fn apply(&mut self, level: u8) -> Result<(), DisplayError> {
+        let duty = gamma_duty(level, self.pwm.max_duty_cycle());
+        self.pwm.set_duty_cycle(duty).map_err(|error| DisplayError::Backlight(error.kind()))?;
+        self.level = level;
+        Ok(())
+    }
+}
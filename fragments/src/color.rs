@@ -0,0 +1,130 @@
+//! Color representation and conversion to panel pixel formats
+
+/// RGB color with components in the range [0, 1]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Color {
+    pub r: f32,
+    pub g: f32,
+    pub b: f32,
+}
+
+impl Color {
+    pub const fn new(r: f32, g: f32, b: f32) -> Self {
+        Self { r, g, b }
+    }
+
+    /// Convert to the 18-bit RGB666 pixel format of the display
+    ///
+    /// Each channel is sent as one byte of which the panel only uses the upper six bits.
+    pub fn to_rgb666(self) -> [u8; 3] {
+        [
+            (self.r * 255.0f32) as u8 & 0xFC,
+            (self.g * 255.0f32) as u8 & 0xFC,
+            (self.b * 255.0f32) as u8 & 0xFC,
+        ]
+    }
+
+    /// Convert to the 16-bit RGB565 pixel format, most significant byte first
+    pub fn to_rgb565(self) -> [u8; 2] {
+        let r = ((self.r * 255.0f32) as u8 >> 3) as u16;
+        let g = ((self.g * 255.0f32) as u8 >> 2) as u16;
+        let b = ((self.b * 255.0f32) as u8 >> 3) as u16;
+        ((r << 11) | (g << 5) | b).to_be_bytes()
+    }
+
+    /// Convert to the 12-bit RGB444 pixel format, returned in the lower 12 bits
+    pub fn to_rgb444(self) -> u16 {
+        let r = ((self.r * 255.0f32) as u8 >> 4) as u16;
+        let g = ((self.g * 255.0f32) as u8 >> 4) as u16;
+        let b = ((self.b * 255.0f32) as u8 >> 4) as u16;
+        (r << 8) | (g << 4) | b
+    }
+}
+
+/// Pixel formats the panel accepts over its serial interface
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum PixelFormat {
+    /// 12 bits per pixel, two pixels packed into three bytes
+    Rgb444,
+    /// 16 bits per pixel in two bytes
+    Rgb565,
+    /// 18 bits per pixel in three bytes, one per channel
+    #[default]
+    Rgb666,
+}
+
+impl PixelFormat {
+    /// Value of the COLMOD register that selects this format
+    pub const fn colmod(self) -> u8 {
+        match self {
+            PixelFormat::Rgb444 => 0x03,
+            PixelFormat::Rgb565 => 0x05,
+            PixelFormat::Rgb666 => 0x06,
+        }
+    }
+
+    /// Number of bytes taken by `pixels` consecutive pixels
+    ///
+    /// For `Rgb444`, `pixels` has to be even.
+    pub const fn bytes_for(self, pixels: usize) -> usize {
+        match self {
+            PixelFormat::Rgb444 => pixels * 3 / 2,
+            PixelFormat::Rgb565 => pixels * 2,
+            PixelFormat::Rgb666 => pixels * 3,
+        }
+    }
+
+    /// Encode consecutive colors into `bytes`, stopping when `bytes` is full
+    ///
+    /// Missing colors are encoded as black.
+    pub fn encode(self, bytes: &mut [u8], mut colors: impl Iterator<Item = Color>) {
+        match self {
+            PixelFormat::Rgb444 => {
+                for pair in bytes.chunks_exact_mut(3) {
+                    let a = colors.next().unwrap_or_default().to_rgb444();
+                    let b = colors.next().unwrap_or_default().to_rgb444();
+                    pair.copy_from_slice(&[(a >> 4) as u8, ((a << 4) | (b >> 8)) as u8, b as u8]);
+                }
+            }
+            PixelFormat::Rgb565 => {
+                for pixel in bytes.chunks_exact_mut(2) {
+                    pixel.copy_from_slice(&colors.next().unwrap_or_default().to_rgb565());
+                }
+            }
+            PixelFormat::Rgb666 => {
+                for pixel in bytes.chunks_exact_mut(3) {
+                    pixel.copy_from_slice(&colors.next().unwrap_or_default().to_rgb666());
+                }
+            }
+        }
+    }
+
+    /// Decode pixels from `bytes` into 8-bit RGB triples in `rgb`
+    ///
+    /// Channels are left-aligned, so the unused low bits are zero as on the wire.
+    pub fn decode(self, bytes: &[u8], rgb: &mut [u8]) {
+        let mut pixels = rgb.chunks_exact_mut(3);
+        match self {
+            PixelFormat::Rgb444 => {
+                for pair in bytes.chunks_exact(3) {
+                    let a = ((pair[0] as u16) << 4) | (pair[1] as u16 >> 4);
+                    let b = (((pair[1] & 0x0F) as u16) << 8) | pair[2] as u16;
+                    for (value, pixel) in [a, b].into_iter().zip(pixels.by_ref()) {
+                        pixel.copy_from_slice(&[(value >> 4) as u8 & 0xF0, value as u8 & 0xF0, (value << 4) as u8]);
+                    }
+                }
+            }
+            PixelFormat::Rgb565 => {
+                for (pixel, bytes) in pixels.zip(bytes.chunks_exact(2)) {
+                    let value = u16::from_be_bytes([bytes[0], bytes[1]]);
+                    pixel.copy_from_slice(&[(value >> 8) as u8 & 0xF8, (value >> 3) as u8 & 0xFC, (value << 3) as u8]);
+                }
+            }
+            PixelFormat::Rgb666 => {
+                for (pixel, bytes) in pixels.zip(bytes.chunks_exact(3)) {
+                    pixel.copy_from_slice(&[bytes[0] & 0xFC, bytes[1] & 0xFC, bytes[2] & 0xFC]);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,940 @@
+//! Display Driver
+//!
+//! Driver for SPI panels with an ST7789, ILI9341 or GC9A01 controller, such as the Waveshare
+//! Pico LCD 2 inch display, integrated with a DMA channel for double buffering.
+
+use embedded_hal::digital::{Error as PinError, ErrorKind as PinErrorKind, OutputPin};
+use embedded_hal::delay::DelayNs;
+use embedded_hal::pwm::{ErrorKind as PwmErrorKind, SetDutyCycle};
+use embedded_hal::spi::{Error as SpiError, ErrorKind as SpiErrorKind, SpiBus};
+
+use crate::backlight::{Backlight, NoBacklight};
+use crate::dma::{DmaChannel, DmaTransfer};
+use crate::color::PixelFormat;
+use crate::framebuffer::Rect;
+use crate::panel::{InitCommand, PanelController, St7789};
+use crate::scroll::ScrollArea;
+
+/// MIPI DCS commands shared by all supported controllers
+#[repr(u8)]
+pub(crate) enum Command {
+    SwReset = 0x01,
+    SlpIn = 0x10,
+    SlpOut = 0x11,
+    NorOn = 0x13,
+    DispOff = 0x28,
+    DispOn = 0x29,
+    CaSet = 0x2A,
+    RaSet = 0x2B,
+    RamWr = 0x2C,
+    VScrDef = 0x33,
+    MadCtl = 0x36,
+    VScSAd = 0x37,
+    ColMod = 0x3A,
+}
+
+
+/// Errors reported by the display driver
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DisplayError {
+    /// The SPI bus failed to transmit
+    Bus(SpiErrorKind),
+    /// Driving the CS, DC or RST pin failed
+    Pin(PinErrorKind),
+    /// Setting the backlight duty cycle failed
+    Backlight(PwmErrorKind),
+    /// The display has not been initialized successfully
+    Uninitialized,
+    /// A DMA transfer still owns the bus
+    TransferInFlight,
+    /// A frame buffer does not have the size required by the pixel format
+    BufferSize,
+    /// An update region is empty, exceeds the display or does not start and end on whole bytes,
+    /// or a scroll area does not fit the display
+    Region,
+    /// A strip was pushed without `begin_frame` or beyond the end of the frame
+    FrameOverrun,
+    /// Every buffer is being transmitted or queued; try again once a transfer has finished
+    NoFreeBuffer,
+    /// The panel cannot be driven in the selected pixel format
+    UnsupportedFormat,
+    /// The display is asleep; `wake` it before sending frames
+    Asleep,
+}
+
+/// CASET/RASET parameters: first and last address, big-endian
+pub(crate) fn address_range(start: u16, end: u16) -> [u8; 4] {
+    let [start_hi, start_lo] = start.to_be_bytes();
+    let [end_hi, end_lo] = end.to_be_bytes();
+    [start_hi, start_lo, end_hi, end_lo]
+}
+
+/// MADCTL with the value that maps frames in `orientation` onto `panel`
+pub(crate) fn madctl_command<P: PanelController>(panel: &P, orientation: Orientation) -> InitCommand {
+    InitCommand::new(Command::MadCtl as u8, &[panel.madctl(orientation)])
+}
+
+/// Command `index` of the sequence that brings `panel` up after a hardware reset, in the
+/// pixel format selected by `colmod` and in `orientation`, or `None` past its end
+///
+/// Both drivers send it, then the address window, a cleared frame and DISPON.
+pub(crate) fn init_command<P: PanelController>(panel: &P, colmod: u8, orientation: Orientation, index: usize) -> Option<InitCommand> {
+    let command = match index {
+        0 => InitCommand::new(Command::SwReset as u8, &[]).with_delay(150),
+        1 => InitCommand::new(Command::SlpOut as u8, &[]).with_delay(150),
+        2 => InitCommand::new(Command::ColMod as u8, &[colmod]),
+        3 => madctl_command(panel, orientation),
+        // Vendor commands: inversion, power and gamma settings of the panel
+        _ => return panel.init_sequence().get(index - 4).copied(),
+    };
+    Some(command)
+}
+
+/// CASET and RASET that make RAMWR fill `window`
+///
+/// `window` is in frame coordinates; the panel's offset in frame memory is added here.
+pub(crate) fn window_commands<P: PanelController>(panel: &P, orientation: Orientation, window: Rect) -> [InitCommand; 2] {
+    let (x, y) = panel.window_offset(orientation);
+    [
+        InitCommand::new(Command::CaSet as u8, &address_range(x + window.x, x + window.right() - 1)),
+        InitCommand::new(Command::RaSet as u8, &address_range(y + window.y, y + window.bottom() - 1)),
+    ]
+}
+
+pub(crate) fn bus_error<E: SpiError>(error: E) -> DisplayError {
+    DisplayError::Bus(error.kind())
+}
+
+pub(crate) fn pin_error<E: PinError>(error: E) -> DisplayError {
+    DisplayError::Pin(error.kind())
+}
+
+
+/// Clockwise rotation of the image relative to the panel's native portrait orientation
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Rotation {
+    #[default]
+    Deg0,
+    Deg90,
+    Deg180,
+    Deg270,
+}
+
+/// How frame buffer rows and columns are mapped onto the panel
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Orientation {
+    pub rotation: Rotation,
+    /// Mirror the rotated image horizontally
+    pub mirrored: bool,
+}
+
+impl Orientation {
+    pub const fn new(rotation: Rotation, mirrored: bool) -> Self {
+        Self { rotation, mirrored }
+    }
+
+    /// Whether the image is rotated by a quarter turn, exchanging width and height
+    pub const fn is_landscape(self) -> bool {
+        matches!(self.rotation, Rotation::Deg90 | Rotation::Deg270)
+    }
+
+    /// Width and height of the image for a panel of the given native size
+    pub const fn size(self, width: u16, height: u16) -> (u16, u16) {
+        if self.is_landscape() { (height, width) } else { (width, height) }
+    }
+
+    /// MADCTL register value: MY (0x80) and MX (0x40) mirror rows and columns of the panel,
+    /// MV (0x20) exchanges rows and columns
+    pub const fn madctl(self) -> u8 {
+        let madctl = match self.rotation {
+            Rotation::Deg0 => 0x00,
+            Rotation::Deg90 => 0x60,
+            Rotation::Deg180 => 0xC0,
+            Rotation::Deg270 => 0xA0,
+        };
+        // Image columns run along panel rows when exchanged, so mirror those instead
+        match (self.mirrored, self.is_landscape()) {
+            (false, _) => madctl,
+            (true, false) => madctl ^ 0x40,
+            (true, true) => madctl ^ 0x80,
+        }
+    }
+}
+
+
+/// Double- or triple-buffered display driver
+///
+/// The driver owns every frame buffer except the one the user renders into: the one being
+/// transmitted, a frame queued behind it, and idle ones, e.g. after an error the one that was
+/// transmitted last. The user's buffer is exchanged on every `swap_buffers` or `queue_frame`,
+/// so no buffer is lost when an operation fails.
+pub struct Display<
+    P: PanelController,
+    SPI: SpiBus,
+    CS: OutputPin,
+    DC: OutputPin,
+    RST: OutputPin,
+    DMACH: DmaChannel<SPI>,
+    BL: SetDutyCycle = NoBacklight,
+> {
+    panel: P,
+    spi: Option<SPI>,
+    cs: CS,
+    dc: DC,
+    rst: RST,
+    dma_ch: Option<DMACH>,
+    transfer: Option<DMACH::Transfer>,
+    /// A strip is chained behind the one being transmitted
+    chained: bool,
+    idle_buffer: Option<&'static mut [u8]>,
+    /// Third buffer for triple buffering, idle like `idle_buffer`
+    spare_buffer: Option<&'static mut [u8]>,
+    /// Rendered frame waiting for the transfer in flight to finish
+    queued: Option<&'static mut [u8]>,
+    pixel_format: PixelFormat,
+    orientation: Orientation,
+    /// Address window last programmed with CASET/RASET
+    window: Rect,
+    /// Bytes still expected by the frame being streamed with `push_strip`
+    stream_remaining: usize,
+    initialized: bool,
+    /// Sleep mode was entered with `sleep`
+    asleep: bool,
+    /// The panel shows its frame memory, i.e. it is not blanked with `display_off`
+    display_enabled: bool,
+    /// Area defined with `define_scroll_area`, while in vertical scrolling mode
+    scroll_area: Option<ScrollArea>,
+    scroll_offset: u16,
+    backlight: Backlight<BL>,
+    /// Brightness to restore on `wake`
+    wake_brightness: u8,
+}
+
+/// Driver for the Waveshare Pico LCD 2, created with `St7789::LCD_240X320`
+pub type WaveshareST7789Display<SPI, CS, DC, RST, DMACH, BL = NoBacklight> = Display<St7789, SPI, CS, DC, RST, DMACH, BL>;
+
+impl<P: PanelController, SPI: SpiBus, CS: OutputPin, DC: OutputPin, RST: OutputPin, DMACH: DmaChannel<SPI>> Display<P, SPI, CS, DC, RST, DMACH> {
+    /// Create a new display driver with DMA support for `panel`
+    ///
+    /// `buffer` becomes the driver's half of the double buffer; the caller keeps the other one.
+    pub fn new(
+        panel: P,
+        spi: SPI, 
+        cs: CS, 
+        dc: DC, 
+        rst: RST, 
+        dma_ch: DMACH,
+        buffer: &'static mut [u8],
+    ) -> Self {
+        Self {
+            panel,
+            spi: Some(spi),
+            cs,
+            dc,
+            rst,
+            dma_ch: Some(dma_ch),
+            transfer: None,
+            chained: false,
+            idle_buffer: Some(buffer),
+            spare_buffer: None,
+            queued: None,
+            pixel_format: PixelFormat::Rgb666,
+            orientation: Orientation::new(Rotation::Deg0, false),
+            window: Rect::default(),
+            stream_remaining: 0,
+            initialized: false,
+            asleep: false,
+            display_enabled: false,
+            scroll_area: None,
+            scroll_offset: 0,
+            backlight: Backlight::new(NoBacklight),
+            wake_brightness: 0,
+        }
+    }
+
+    /// Control the backlight with the PWM channel `pwm`
+    ///
+    /// The backlight stays dark until its brightness is set.
+    pub fn with_backlight<BL: SetDutyCycle>(self, pwm: BL) -> Display<P, SPI, CS, DC, RST, DMACH, BL> {
+        Display {
+            panel: self.panel,
+            spi: self.spi,
+            cs: self.cs,
+            dc: self.dc,
+            rst: self.rst,
+            dma_ch: self.dma_ch,
+            transfer: self.transfer,
+            chained: self.chained,
+            idle_buffer: self.idle_buffer,
+            spare_buffer: self.spare_buffer,
+            queued: self.queued,
+            pixel_format: self.pixel_format,
+            orientation: self.orientation,
+            window: self.window,
+            stream_remaining: self.stream_remaining,
+            initialized: self.initialized,
+            asleep: self.asleep,
+            display_enabled: self.display_enabled,
+            scroll_area: self.scroll_area,
+            scroll_offset: self.scroll_offset,
+            backlight: Backlight::new(pwm),
+            wake_brightness: 0,
+        }
+    }
+}
+
+impl<P: PanelController, SPI: SpiBus, CS: OutputPin, DC: OutputPin, RST: OutputPin, DMACH: DmaChannel<SPI>, BL: SetDutyCycle>
+    Display<P, SPI, CS, DC, RST, DMACH, BL>
+{
+
+    /// Select the pixel format sent to the panel, RGB666 by default
+    ///
+    /// Takes effect with the next `init`. Frame buffers must be `PanelController::buffer_size(format)`
+    /// bytes long.
+    pub fn with_pixel_format(mut self, format: PixelFormat) -> Self {
+        self.pixel_format = format;
+        self
+    }
+
+    /// Hand the driver a third frame buffer for triple buffering
+    ///
+    /// With a frame in flight and another one queued, `queue_frame` then still has a buffer to
+    /// return, so rendering never waits for the display.
+    pub fn with_spare_buffer(mut self, buffer: &'static mut [u8]) -> Self {
+        self.spare_buffer = Some(buffer);
+        self
+    }
+
+    /// Pixel format the frame buffers have to be encoded in
+    pub fn pixel_format(&self) -> PixelFormat {
+        self.pixel_format
+    }
+
+    /// Select how frames are mapped onto the panel, portrait by default
+    ///
+    /// Takes effect with the next `init`; use `set_orientation` to change it afterwards.
+    pub fn with_orientation(mut self, orientation: Orientation) -> Self {
+        self.orientation = orientation;
+        self
+    }
+
+    pub fn orientation(&self) -> Orientation {
+        self.orientation
+    }
+
+    pub fn panel(&self) -> &P {
+        &self.panel
+    }
+
+    /// Width of a frame in the current orientation
+    pub fn width(&self) -> u16 {
+        let (width, height) = self.panel.size();
+        self.orientation.size(width, height).0
+    }
+
+    /// Height of a frame in the current orientation
+    pub fn height(&self) -> u16 {
+        let (width, height) = self.panel.size();
+        self.orientation.size(width, height).1
+    }
+
+    /// Perceived backlight brightness, out of 255
+    pub fn brightness(&self) -> u8 {
+        self.backlight.brightness()
+    }
+
+    /// Set the perceived backlight brightness, out of 255, stopping a fade in progress
+    pub fn set_brightness(&mut self, level: u8) -> Result<(), DisplayError> {
+        self.backlight.set_brightness(level)
+    }
+
+    /// Fade the backlight to `level` over `duration_us`, starting at the counter value `now_us`
+    ///
+    /// The fade advances with `update_backlight`, e.g. once per frame.
+    pub fn fade_brightness(&mut self, level: u8, duration_us: u32, now_us: u64) {
+        self.backlight.fade_to(level, duration_us, now_us);
+    }
+
+    /// Advance a backlight fade to the counter value `now_us`
+    pub fn update_backlight(&mut self, now_us: u64) -> Result<(), DisplayError> {
+        self.backlight.update(now_us)
+    }
+
+    /// Change the orientation of an initialized display
+    ///
+    /// Waits for the frame being transmitted, stops scrolling, then reprograms MADCTL and the
+    /// address window. Subsequent frames have to be rendered at the new `width` and `height`.
+    pub fn set_orientation<DELAY: DelayNs>(&mut self, delay: &mut DELAY, orientation: Orientation) -> Result<(), DisplayError> {
+        if !self.initialized {
+            return Err(DisplayError::Uninitialized);
+        }
+        self.stop_scrolling(delay)?;
+        self.orientation = orientation;
+        self.send_command(delay, &madctl_command(&self.panel, orientation))?;
+        self.set_window(delay, Rect::full(self.width(), self.height()))
+    }
+
+    /// Reset and initialize the display, clearing it to black
+    ///
+    /// May be called again to recover the panel after an error. Fails with
+    /// `TransferInFlight` if a frame is still being transmitted, and with `UnsupportedFormat`
+    /// if the panel does not accept the pixel format.
+    pub fn init<DELAY: DelayNs>(&mut self, delay: &mut DELAY) -> Result<(), DisplayError> {
+        if self.transfer.as_ref().is_some_and(|transfer| !transfer.is_done()) {
+            return Err(DisplayError::TransferInFlight);
+        }
+        self.initialized = false;
+        self.asleep = false;
+        self.display_enabled = false;
+        self.scroll_area = None;
+        self.scroll_offset = 0;
+        self.finish_transfer()?;
+        self.discard_queued();
+        let colmod = self.panel.colmod(self.pixel_format).ok_or(DisplayError::UnsupportedFormat)?;
+        let max_size = self.panel.buffer_size(self.pixel_format);
+        let wrong_size = |buffer: &Option<&'static mut [u8]>| buffer.as_ref().is_some_and(|b| b.is_empty() || b.len() > max_size);
+        if wrong_size(&self.idle_buffer) || wrong_size(&self.spare_buffer) {
+            return Err(DisplayError::BufferSize);
+        }
+
+        self.cs.set_high().map_err(pin_error)?;
+        self.hard_reset(delay)?;
+
+        let mut index = 0;
+        while let Some(command) = init_command(&self.panel, colmod, self.orientation, index) {
+            self.send_command(delay, &command)?;
+            index += 1;
+        }
+
+        self.set_window(delay, Rect::full(self.width(), self.height()))?;
+
+        // Clear the display with our buffer, which may only hold a strip of the frame
+        let buffer = self.take_idle().ok_or(DisplayError::Uninitialized)?;
+        buffer.fill(0);
+        self.write_command(delay, Command::RamWr as u8)?;
+        delay.delay_ms(1);
+        let mut remaining = self.panel.buffer_size(self.pixel_format);
+        let mut result = Ok(());
+        while remaining > 0 && result.is_ok() {
+            let len = remaining.min(buffer.len());
+            result = self.write_data(delay, &buffer[..len]);
+            remaining -= len;
+        }
+        self.put_idle(buffer);
+        result?;
+        delay.delay_ms(1);
+
+        self.write_command(delay, Command::DispOn as u8)?;
+        delay.delay_ms(120);
+
+        self.initialized = true;
+        self.display_enabled = true;
+        Ok(())
+    }
+
+    /// Enter sleep mode, in which the panel draws the least power and keeps its frame memory
+    ///
+    /// Waits for the frame being transmitted, drops a queued one and switches the backlight
+    /// off. Frames are rejected with `Asleep` until `wake`.
+    pub fn sleep<DELAY: DelayNs>(&mut self, delay: &mut DELAY) -> Result<(), DisplayError> {
+        if !self.initialized {
+            return Err(DisplayError::Uninitialized);
+        }
+        if self.asleep {
+            return Ok(());
+        }
+        self.finish_transfer()?;
+        self.discard_queued();
+        let brightness = self.backlight.brightness();
+        self.backlight.set_brightness(0)?;
+        self.wake_brightness = brightness;
+        if let Err(error) = self.write_command(delay, Command::SlpIn as u8) {
+            // The panel is still awake, so it must not stay dark
+            self.backlight.set_brightness(brightness)?;
+            return Err(error);
+        }
+        // SLPOUT may only follow 120 ms after SLPIN
+        delay.delay_ms(120);
+        self.asleep = true;
+        Ok(())
+    }
+
+    /// Leave sleep mode and restore the backlight brightness from before `sleep`
+    pub fn wake<DELAY: DelayNs>(&mut self, delay: &mut DELAY) -> Result<(), DisplayError> {
+        if !self.initialized {
+            return Err(DisplayError::Uninitialized);
+        }
+        if !self.asleep {
+            return Ok(());
+        }
+        self.write_command(delay, Command::SlpOut as u8)?;
+        // The supply voltages settle within 120 ms, before which SLPIN must not follow either
+        delay.delay_ms(120);
+        self.asleep = false;
+        self.backlight.set_brightness(self.wake_brightness)
+    }
+
+    pub fn is_asleep(&self) -> bool {
+        self.asleep
+    }
+
+    /// Blank the panel while keeping its frame memory, backlight and the frame pipeline running
+    ///
+    /// Waits for the frame being transmitted. Frames sent while the display is off show up
+    /// with `display_on`. Dim the backlight as well to save its power.
+    pub fn display_off<DELAY: DelayNs>(&mut self, delay: &mut DELAY) -> Result<(), DisplayError> {
+        self.set_display_enabled(delay, false)
+    }
+
+    /// Show the frame memory again after `display_off`
+    pub fn display_on<DELAY: DelayNs>(&mut self, delay: &mut DELAY) -> Result<(), DisplayError> {
+        self.set_display_enabled(delay, true)
+    }
+
+    pub fn is_display_on(&self) -> bool {
+        self.display_enabled
+    }
+
+    fn set_display_enabled<DELAY: DelayNs>(&mut self, delay: &mut DELAY, enabled: bool) -> Result<(), DisplayError> {
+        if !self.initialized {
+            return Err(DisplayError::Uninitialized);
+        }
+        self.finish_transfer()?;
+        let command = if enabled { Command::DispOn } else { Command::DispOff };
+        self.write_command(delay, command as u8)?;
+        self.display_enabled = enabled;
+        Ok(())
+    }
+
+    /// Enter vertical scrolling mode with `area` and show it unscrolled
+    ///
+    /// Waits for the frame being transmitted. Frames keep being written in frame coordinates,
+    /// while the panel shows the scroll area rotated by the offset set with `scroll_to`. The
+    /// panel scrolls along its native rows, so this fails with `Region` in landscape and if
+    /// `area` does not add up to the display height.
+    pub fn define_scroll_area<DELAY: DelayNs>(&mut self, delay: &mut DELAY, area: ScrollArea) -> Result<(), DisplayError> {
+        if !self.initialized {
+            return Err(DisplayError::Uninitialized);
+        }
+        let rows = self.panel.scroll_definition(self.orientation, area).ok_or(DisplayError::Region)?;
+        self.finish_transfer()?;
+        let mut parameters = [0; 6];
+        for (bytes, value) in parameters.chunks_exact_mut(2).zip(rows) {
+            bytes.copy_from_slice(&value.to_be_bytes());
+        }
+        self.write_command(delay, Command::VScrDef as u8)?;
+        self.write_data(delay, &parameters)?;
+        self.scroll_area = Some(area);
+        self.scroll_to(delay, 0)
+    }
+
+    /// Scroll the content of the scroll area up by `offset` rows, wrapping around
+    ///
+    /// Row `row` of the scroll area then shows `ScrollArea::source_row(row, offset)` of the
+    /// frame. Takes a single command instead of a frame; fails with `Region` without a scroll
+    /// area.
+    pub fn scroll_to<DELAY: DelayNs>(&mut self, delay: &mut DELAY, offset: u16) -> Result<(), DisplayError> {
+        let area = self.scroll_area.ok_or(DisplayError::Region)?;
+        self.finish_transfer()?;
+        let offset = offset % area.scroll_height;
+        let start = self.panel.scroll_start(self.orientation, area, offset);
+        self.write_command(delay, Command::VScSAd as u8)?;
+        self.write_data(delay, &start.to_be_bytes())?;
+        self.scroll_offset = offset;
+        Ok(())
+    }
+
+    /// Leave vertical scrolling mode, showing the frame memory as written
+    pub fn stop_scrolling<DELAY: DelayNs>(&mut self, delay: &mut DELAY) -> Result<(), DisplayError> {
+        self.finish_transfer()?;
+        if self.scroll_area.is_none() {
+            return Ok(());
+        }
+        self.write_command(delay, Command::NorOn as u8)?;
+        self.scroll_area = None;
+        self.scroll_offset = 0;
+        Ok(())
+    }
+
+    /// Area defined with `define_scroll_area`, unless scrolling has been stopped
+    pub fn scroll_area(&self) -> Option<ScrollArea> {
+        self.scroll_area
+    }
+
+    /// Offset last set with `scroll_to`
+    pub fn scroll_offset(&self) -> u16 {
+        self.scroll_offset
+    }
+
+    /// Swap buffers: submit the filled `buffer` for DMA transfer and get the other buffer back in its place
+    /// 
+    /// This achieves true parallelism:
+    /// 1. Wait for current transfer to complete
+    /// 2. Start new DMA transfer with the buffer you provide
+    /// 3. Hand you the buffer that just finished to fill
+    ///
+    /// On error, `buffer` is left untouched and can be submitted again, e.g. after `init`.
+    pub fn swap_buffers<DELAY: DelayNs>(&mut self, delay: &mut DELAY, buffer: &mut &'static mut [u8]) -> Result<(), DisplayError> {
+        self.submit(delay, Rect::full(self.width(), self.height()), buffer)
+    }
+
+    /// Queue the filled `buffer` for transmission without waiting and get a free buffer back in its place
+    ///
+    /// The frame is sent right away if the bus is idle. Otherwise it is queued, and sent by
+    /// `on_transfer_complete` once the frame in flight is done. A frame that is still queued is
+    /// replaced by the newer one and handed back for rendering, so with triple buffering this
+    /// never fails with `NoFreeBuffer`. On error, `buffer` is left untouched.
+    pub fn queue_frame<DELAY: DelayNs>(&mut self, delay: &mut DELAY, buffer: &mut &'static mut [u8]) -> Result<(), DisplayError> {
+        if !self.initialized {
+            return Err(DisplayError::Uninitialized);
+        }
+        if self.asleep {
+            return Err(DisplayError::Asleep);
+        }
+        if buffer.len() != self.panel.buffer_size(self.pixel_format) {
+            return Err(DisplayError::BufferSize);
+        }
+        if self.stream_remaining > 0 {
+            return Err(DisplayError::TransferInFlight);
+        }
+
+        // The completion interrupt may not have been handled yet
+        if self.transfer.as_ref().is_some_and(|transfer| transfer.is_done()) {
+            self.finish_transfer()?;
+        }
+        if self.transfer.is_none() {
+            return self.submit(delay, Rect::full(self.width(), self.height()), buffer);
+        }
+
+        let free = match self.queued.take() {
+            Some(stale) => stale,
+            None => self.take_idle().ok_or(DisplayError::NoFreeBuffer)?,
+        };
+        self.queued = Some(core::mem::replace(buffer, free));
+        Ok(())
+    }
+
+    /// Whether `queue_frame` would find a buffer to hand back
+    pub fn buffer_free(&self) -> bool {
+        self.queued.is_some()
+            || self.idle_buffer.is_some()
+            || self.spare_buffer.is_some()
+            || self.transfer.as_ref().is_some_and(|transfer| transfer.is_done())
+    }
+
+    /// Clear the completion interrupt of the display's DMA channel, returning whether it was raised
+    ///
+    /// Call it first thing in the interrupt handler, before `on_transfer_complete`; the
+    /// interrupt fires again as soon as the handler returns otherwise.
+    pub fn acknowledge_interrupt(&mut self) -> bool {
+        match (&mut self.transfer, &mut self.dma_ch) {
+            (Some(transfer), _) => transfer.ack_irq(),
+            (None, Some(dma_ch)) => dma_ch.ack_irq(),
+            (None, None) => false,
+        }
+    }
+
+    /// Handle the end of a DMA transfer, typically from the DMA completion interrupt
+    ///
+    /// Deselects the display and frees the transmitted buffer. If `start_next` is set, a frame
+    /// waiting in the queue is transmitted right away. Does nothing while the transfer is still
+    /// running, and leaves the display selected between the strips of a streamed frame.
+    pub fn on_transfer_complete<DELAY: DelayNs>(&mut self, delay: &mut DELAY, start_next: bool) -> Result<(), DisplayError> {
+        if !self.transfer.as_ref().is_some_and(|transfer| transfer.is_done()) {
+            return Ok(());
+        }
+        if self.stream_remaining > 0 {
+            self.reclaim_transfer();
+            return Ok(());
+        }
+        self.finish_transfer()?;
+        if !start_next {
+            return Ok(());
+        }
+        let Some(queued) = self.queued.take() else {
+            return Ok(());
+        };
+
+        let frame = Rect::full(self.width(), self.height());
+        if let Err(error) = self.start_frame(delay, frame) {
+            self.queued = Some(queued);
+            return Err(error);
+        }
+        if let Err(queued) = self.start_transfer(queued, self.pixel_format.bytes_for(frame.area())) {
+            self.queued = Some(queued);
+            return Err(DisplayError::TransferInFlight);
+        }
+        Ok(())
+    }
+
+    /// Like `swap_buffers`, but only update `region` of the display
+    ///
+    /// The start of `buffer` holds the pixels of `region` row by row, e.g. copied out of a full
+    /// frame with `pack_region`; the rest of the buffer is not sent. Both buffers stay full-sized,
+    /// so full and partial updates can be mixed freely. In RGB444, `region` has to start and end
+    /// on even columns, see `Rect::aligned`.
+    pub fn swap_region<DELAY: DelayNs>(&mut self, delay: &mut DELAY, region: Rect, buffer: &mut &'static mut [u8]) -> Result<(), DisplayError> {
+        if region.is_empty() || !region.fits(self.width(), self.height()) || region.aligned(self.pixel_format) != region {
+            return Err(DisplayError::Region);
+        }
+        self.submit(delay, region, buffer)
+    }
+
+    /// Start streaming a frame strip by strip with `push_strip`
+    ///
+    /// Streaming needs no full-frame buffers: the driver can be created with a buffer of a few
+    /// rows, and the cores render the next strip while the previous one is transmitted.
+    pub fn begin_frame<DELAY: DelayNs>(&mut self, delay: &mut DELAY) -> Result<(), DisplayError> {
+        if !self.initialized {
+            return Err(DisplayError::Uninitialized);
+        }
+        if self.asleep {
+            return Err(DisplayError::Asleep);
+        }
+        self.finish_transfer()?;
+        let frame = Rect::full(self.width(), self.height());
+        self.start_frame(delay, frame)?;
+        self.stream_remaining = self.pixel_format.bytes_for(frame.area());
+        Ok(())
+    }
+
+    /// Transmit the next `rows` rows of the frame from the start of `strip` and get a strip
+    /// sent before back in its place
+    ///
+    /// Strips follow each other in one continuous RAMWR, so they must be pushed top to bottom
+    /// and add up to exactly one frame. Any number of strip buffers can be rotated through the
+    /// driver this way. If the DMA channel can chain transfers, `strip` is queued behind the
+    /// one being transmitted so the bus never idles between strips, and with a spare buffer
+    /// (`with_spare_buffer`) a free strip comes back without waiting; otherwise each strip is
+    /// started once the one before has been sent. On error, `strip` is left untouched and the
+    /// frame has to be restarted with `begin_frame`.
+    pub fn push_strip(&mut self, strip: &mut &'static mut [u8], rows: usize) -> Result<(), DisplayError> {
+        let len = rows * self.pixel_format.bytes_for(self.width() as usize);
+        if len > strip.len() {
+            return Err(DisplayError::BufferSize);
+        }
+        if len > self.stream_remaining {
+            return Err(DisplayError::FrameOverrun);
+        }
+        if self.transfer.is_none() && self.idle_buffer.is_none() && self.spare_buffer.is_none() {
+            return Err(DisplayError::Uninitialized);
+        }
+
+        // The display stays selected in data mode between strips. Only one strip is chained at
+        // a time, so wait for the one ahead of it to be sent.
+        self.wait_sent();
+        let ready_strip = core::mem::take(strip);
+        let unchained = match self.transfer.as_mut() {
+            Some(transfer) => transfer.chain(ready_strip, len).err(),
+            None => Some(ready_strip),
+        };
+        match unchained {
+            Some(ready_strip) => {
+                self.reclaim_transfer();
+                if let Err(ready_strip) = self.start_transfer(ready_strip, len) {
+                    *strip = ready_strip;
+                    return Err(DisplayError::TransferInFlight);
+                }
+            }
+            None => self.chained = true,
+        }
+        self.stream_remaining -= len;
+
+        if self.idle_buffer.is_none() && self.spare_buffer.is_none() {
+            self.wait_sent();
+        }
+        // The driver had a buffer idle, reclaimed the one sent before `strip` started, or has
+        // just waited for the one ahead of the chained `strip`
+        *strip = self.take_idle().expect("a strip buffer is idle after queueing another one");
+        Ok(())
+    }
+
+    /// Send the pixels of `region` from the start of `buffer` and take back the idle buffer
+    fn submit<DELAY: DelayNs>(&mut self, delay: &mut DELAY, region: Rect, buffer: &mut &'static mut [u8]) -> Result<(), DisplayError> {
+        if !self.initialized {
+            return Err(DisplayError::Uninitialized);
+        }
+        if self.asleep {
+            return Err(DisplayError::Asleep);
+        }
+        if buffer.len() != self.panel.buffer_size(self.pixel_format) {
+            return Err(DisplayError::BufferSize);
+        }
+
+        // Step 1: Wait for current transfer to complete and the bus to go idle
+        self.finish_transfer()?;
+        self.discard_queued();
+
+        // Step 2: Address the region and send RAMWR for the next frame
+        self.start_frame(delay, region)?;
+        
+        // Step 3: Start DMA transfer with the ready buffer and hand back the completed one
+        let completed_buffer = self.take_idle().ok_or(DisplayError::Uninitialized)?;
+        let ready_buffer = core::mem::replace(buffer, completed_buffer);
+        if let Err(ready_buffer) = self.start_transfer(ready_buffer, self.pixel_format.bytes_for(region.area())) {
+            self.put_idle(core::mem::replace(buffer, ready_buffer));
+            return Err(DisplayError::TransferInFlight);
+        }
+        Ok(())
+    }
+
+    /// Hand the first `len` bytes of `buffer` to the DMA channel once RAMWR has been sent
+    ///
+    /// Gives `buffer` back if the bus or the channel still belong to a transfer.
+    fn start_transfer(&mut self, buffer: &'static mut [u8], len: usize) -> Result<(), &'static mut [u8]> {
+        match (self.spi.take(), self.dma_ch.take()) {
+            (Some(spi), Some(ch)) => {
+                self.transfer = Some(ch.start(buffer, len, spi));
+                Ok(())
+            }
+            (spi, ch) => {
+                self.spi = spi;
+                self.dma_ch = ch;
+                Err(buffer)
+            }
+        }
+    }
+
+    fn take_idle(&mut self) -> Option<&'static mut [u8]> {
+        self.idle_buffer.take().or_else(|| self.spare_buffer.take())
+    }
+
+    fn put_idle(&mut self, buffer: &'static mut [u8]) {
+        if self.idle_buffer.is_none() {
+            self.idle_buffer = Some(buffer);
+        } else {
+            self.spare_buffer = Some(buffer);
+        }
+    }
+
+    /// Drop a queued frame that a newer one, or a reset, has made obsolete
+    fn discard_queued(&mut self) {
+        if let Some(stale) = self.queued.take() {
+            self.put_idle(stale);
+        }
+    }
+
+    /// Hardware reset the display
+    pub fn hard_reset<DELAY: DelayNs>(&mut self, delay: &mut DELAY) -> Result<(), DisplayError> {
+        self.rst.set_high().map_err(pin_error)?;
+        delay.delay_ms(100);
+        self.rst.set_low().map_err(pin_error)?;
+        delay.delay_ms(100);
+        self.rst.set_high().map_err(pin_error)?;
+        delay.delay_ms(120);
+        Ok(())
+    }
+
+    /// Set the column and row address window that RAMWR fills
+    ///
+    /// `window` is in frame coordinates; the panel's offset in frame memory is added here.
+    fn set_window<DELAY: DelayNs>(&mut self, delay: &mut DELAY, window: Rect) -> Result<(), DisplayError> {
+        // Forget the old window until the new one is fully programmed
+        self.window = Rect::default();
+        for command in window_commands(&self.panel, self.orientation, window) {
+            self.send_command(delay, &command)?;
+        }
+        self.window = window;
+        Ok(())
+    }
+
+    /// Wait for a running transfer to end, take back its resources and deselect the display
+    ///
+    /// This ends any frame being streamed.
+    fn finish_transfer(&mut self) -> Result<(), DisplayError> {
+        self.stream_remaining = 0;
+        if self.reclaim_transfer() {
+            // DMA completion only means the last bytes reached the SPI FIFO
+            if let Some(spi) = self.spi.as_mut() {
+                spi.flush().map_err(bus_error)?;
+            }
+            self.cs.set_high().map_err(pin_error)?;
+        }
+        Ok(())
+    }
+
+    /// Wait for a running transfer to end and take back the bus, the channel and the buffer
+    ///
+    /// Returns whether there was a transfer.
+    fn reclaim_transfer(&mut self) -> bool {
+        self.wait_sent();
+        let Some(transfer) = self.transfer.take() else {
+            return false;
+        };
+        let (ch, completed_buffer, spi) = transfer.wait();
+        self.dma_ch = Some(ch);
+        self.spi = Some(spi);
+        self.put_idle(completed_buffer);
+        true
+    }
+
+    /// Wait for the strip ahead of a chained one to be sent and keep it as an idle buffer
+    fn wait_sent(&mut self) {
+        let Some(transfer) = self.transfer.as_mut().filter(|_| self.chained) else {
+            return;
+        };
+        let sent = loop {
+            if let Some(sent) = transfer.take_sent() {
+                break sent;
+            }
+        };
+        self.chained = false;
+        self.put_idle(sent);
+    }
+
+    /// Address `window` if needed, send RAMWR and leave the display selected in data mode for
+    /// the DMA transfer
+    ///
+    /// DC is only switched once the command has left the SPI peripheral, so no fixed delays
+    /// are needed on the per-frame path.
+    fn start_frame<DELAY: DelayNs>(&mut self, delay: &mut DELAY, window: Rect) -> Result<(), DisplayError> {
+        if window != self.window {
+            self.set_window(delay, window)?;
+        }
+        let spi = self.spi.as_mut().ok_or(DisplayError::TransferInFlight)?;
+        self.dc.set_low().map_err(pin_error)?; // Command mode
+        self.cs.set_low().map_err(pin_error)?; // Select the display
+        spi.write(&[Command::RamWr as u8]).map_err(bus_error)?;
+        spi.flush().map_err(bus_error)?;
+        self.dc.set_high().map_err(pin_error)?;
+        Ok(())
+    }
+
+    /// Send `command` with its parameters and wait as long as it asks for
+    fn send_command<DELAY: DelayNs>(&mut self, delay: &mut DELAY, command: &InitCommand) -> Result<(), DisplayError> {
+        self.write_command(delay, command.command)?;
+        if !command.parameters().is_empty() {
+            self.write_data(delay, command.parameters())?;
+        }
+        if command.delay_ms > 0 {
+            delay.delay_ms(command.delay_ms);
+        }
+        Ok(())
+    }
+
+    /// Write a command to the display
+    fn write_command<DELAY: DelayNs>(&mut self, delay: &mut DELAY, command: u8) -> Result<(), DisplayError> {
+        let spi = self.spi.as_mut().ok_or(DisplayError::TransferInFlight)?;
+        self.dc.set_low().map_err(pin_error)?; // Command mode
+        self.cs.set_low().map_err(pin_error)?; // Select the display
+        delay.delay_ns(100);
+        spi.write(&[command]).map_err(bus_error)?;
+        spi.flush().map_err(bus_error)?;
+        self.cs.set_high().map_err(pin_error)?; // Deselect the display
+        delay.delay_ns(100);
+        Ok(())
+    }
+
+    /// Write data to the display
+    fn write_data<DELAY: DelayNs>(&mut self, delay: &mut DELAY, data: &[u8]) -> Result<(), DisplayError> {
+        let spi = self.spi.as_mut().ok_or(DisplayError::TransferInFlight)?;
+        self.dc.set_high().map_err(pin_error)?; // Data mode
+        self.cs.set_low().map_err(pin_error)?; // Select the display
+        delay.delay_ns(100);
+        spi.write(data).map_err(bus_error)?;
+        spi.flush().map_err(bus_error)?;
+        self.cs.set_high().map_err(pin_error)?; // Deselect the display
+        delay.delay_ns(100);
+        Ok(())
+    }
+}
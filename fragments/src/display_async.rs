@@ -0,0 +1,262 @@
+//! Async Display Driver
+//!
+//! The same panel driver on top of `embedded-hal-async`, for executors such as embassy.
+//! Transfers and delays are awaited instead of blocking, so USB, input polling and rendering
+//! can run as tasks side by side. With embassy-rp, an async `Spi` streams frames by DMA.
+
+use embedded_hal::digital::OutputPin;
+use embedded_hal_async::delay::DelayNs;
+use embedded_hal_async::spi::SpiBus;
+
+use crate::color::PixelFormat;
+use crate::display::{bus_error, init_command, madctl_command, pin_error, window_commands, Command, DisplayError, Orientation, Rotation};
+use crate::framebuffer::Rect;
+use crate::panel::{InitCommand, PanelController, St7789};
+
+/// Zeros sent repeatedly to clear the panel without a frame buffer
+static ZEROS: [u8; 256] = [0; 256];
+
+/// Async display driver
+///
+/// Frames are borrowed only for as long as they are transmitted. To render the next frame
+/// while the current one is sent, await both together, e.g. with embassy's `join`:
+///
+/// ```ignore
+/// let (sent, ()) = join(display.swap_buffers(front), render(back)).await;
+/// ```
+///
+/// If a transfer future is dropped before it completes, the panel is left in the middle of a
+/// RAMWR; the next call simply starts over with a new command.
+pub struct AsyncDisplay<P: PanelController, SPI: SpiBus, CS: OutputPin, DC: OutputPin, RST: OutputPin> {
+    panel: P,
+    spi: SPI,
+    cs: CS,
+    dc: DC,
+    rst: RST,
+    pixel_format: PixelFormat,
+    orientation: Orientation,
+    /// Address window last programmed with CASET/RASET
+    window: Rect,
+    initialized: bool,
+}
+
+/// Async driver for the Waveshare Pico LCD 2, created with `St7789::LCD_240X320`
+pub type AsyncST7789Display<SPI, CS, DC, RST> = AsyncDisplay<St7789, SPI, CS, DC, RST>;
+
+impl<P: PanelController, SPI: SpiBus, CS: OutputPin, DC: OutputPin, RST: OutputPin> AsyncDisplay<P, SPI, CS, DC, RST> {
+    /// Create a new async display driver for `panel`
+    pub fn new(panel: P, spi: SPI, cs: CS, dc: DC, rst: RST) -> Self {
+        Self {
+            panel,
+            spi,
+            cs,
+            dc,
+            rst,
+            pixel_format: PixelFormat::Rgb666,
+            orientation: Orientation::new(Rotation::Deg0, false),
+            window: Rect::default(),
+            initialized: false,
+        }
+    }
+
+    /// Select the pixel format sent to the panel, RGB666 by default
+    ///
+    /// Takes effect with the next `init`. Frames must be `PanelController::buffer_size(format)` bytes long.
+    pub fn with_pixel_format(mut self, format: PixelFormat) -> Self {
+        self.pixel_format = format;
+        self
+    }
+
+    pub fn pixel_format(&self) -> PixelFormat {
+        self.pixel_format
+    }
+
+    /// Select how frames are mapped onto the panel, portrait by default
+    ///
+    /// Takes effect with the next `init`; use `set_orientation` to change it afterwards.
+    pub fn with_orientation(mut self, orientation: Orientation) -> Self {
+        self.orientation = orientation;
+        self
+    }
+
+    pub fn orientation(&self) -> Orientation {
+        self.orientation
+    }
+
+    pub fn panel(&self) -> &P {
+        &self.panel
+    }
+
+    /// Width of a frame in the current orientation
+    pub fn width(&self) -> u16 {
+        let (width, height) = self.panel.size();
+        self.orientation.size(width, height).0
+    }
+
+    /// Height of a frame in the current orientation
+    pub fn height(&self) -> u16 {
+        let (width, height) = self.panel.size();
+        self.orientation.size(width, height).1
+    }
+
+    /// Release the bus and the pins
+    pub fn release(self) -> (SPI, CS, DC, RST) {
+        (self.spi, self.cs, self.dc, self.rst)
+    }
+
+    /// Reset and initialize the display, clearing it to black
+    ///
+    /// May be called again to recover the panel after an error. Fails with `UnsupportedFormat`
+    /// if the panel does not accept the pixel format.
+    pub async fn init<DELAY: DelayNs>(&mut self, delay: &mut DELAY) -> Result<(), DisplayError> {
+        self.initialized = false;
+        let colmod = self.panel.colmod(self.pixel_format).ok_or(DisplayError::UnsupportedFormat)?;
+        self.cs.set_high().map_err(pin_error)?;
+        self.hard_reset(delay).await?;
+
+        let mut index = 0;
+        while let Some(command) = init_command(&self.panel, colmod, self.orientation, index) {
+            self.send_command(delay, &command).await?;
+            index += 1;
+        }
+
+        self.set_window(Rect::full(self.width(), self.height())).await?;
+
+        // Clear the display
+        self.start_frame(Rect::full(self.width(), self.height())).await?;
+        let mut remaining = self.panel.buffer_size(self.pixel_format);
+        while remaining > 0 {
+            let len = remaining.min(ZEROS.len());
+            self.spi.write(&ZEROS[..len]).await.map_err(bus_error)?;
+            remaining -= len;
+        }
+        self.end_frame().await?;
+
+        self.write_command(Command::DispOn as u8).await?;
+        delay.delay_ms(120).await;
+
+        self.initialized = true;
+        Ok(())
+    }
+
+    /// Change the orientation of an initialized display
+    ///
+    /// Subsequent frames have to be rendered at the new `width` and `height`.
+    pub async fn set_orientation(&mut self, orientation: Orientation) -> Result<(), DisplayError> {
+        if !self.initialized {
+            return Err(DisplayError::Uninitialized);
+        }
+        self.orientation = orientation;
+        let command = madctl_command(&self.panel, orientation);
+        self.write_command(command.command).await?;
+        self.write_data(command.parameters()).await?;
+        self.set_window(Rect::full(self.width(), self.height())).await
+    }
+
+    /// Transmit a full frame, completing once it has left the SPI peripheral
+    pub async fn swap_buffers(&mut self, frame: &[u8]) -> Result<(), DisplayError> {
+        if !self.initialized {
+            return Err(DisplayError::Uninitialized);
+        }
+        if frame.len() != self.panel.buffer_size(self.pixel_format) {
+            return Err(DisplayError::BufferSize);
+        }
+        self.write_pixels(Rect::full(self.width(), self.height()), frame).await
+    }
+
+    /// Like `swap_buffers`, but only update `region` of the display
+    ///
+    /// `pixels` holds the pixels of `region` row by row, e.g. copied out of a full frame with
+    /// `pack_region`. In RGB444, `region` has to start and end on even columns.
+    pub async fn swap_region(&mut self, region: Rect, pixels: &[u8]) -> Result<(), DisplayError> {
+        if !self.initialized {
+            return Err(DisplayError::Uninitialized);
+        }
+        if region.is_empty() || !region.fits(self.width(), self.height()) || region.aligned(self.pixel_format) != region {
+            return Err(DisplayError::Region);
+        }
+        let len = self.pixel_format.bytes_for(region.area());
+        if pixels.len() < len {
+            return Err(DisplayError::BufferSize);
+        }
+        self.write_pixels(region, &pixels[..len]).await
+    }
+
+    /// Hardware reset the display
+    pub async fn hard_reset<DELAY: DelayNs>(&mut self, delay: &mut DELAY) -> Result<(), DisplayError> {
+        self.rst.set_high().map_err(pin_error)?;
+        delay.delay_ms(100).await;
+        self.rst.set_low().map_err(pin_error)?;
+        delay.delay_ms(100).await;
+        self.rst.set_high().map_err(pin_error)?;
+        delay.delay_ms(120).await;
+        Ok(())
+    }
+
+    async fn write_pixels(&mut self, window: Rect, pixels: &[u8]) -> Result<(), DisplayError> {
+        self.start_frame(window).await?;
+        self.spi.write(pixels).await.map_err(bus_error)?;
+        self.end_frame().await
+    }
+
+    /// Set the column and row address window that RAMWR fills
+    async fn set_window(&mut self, window: Rect) -> Result<(), DisplayError> {
+        // Forget the old window until the new one is fully programmed
+        self.window = Rect::default();
+        for command in window_commands(&self.panel, self.orientation, window) {
+            self.write_command(command.command).await?;
+            self.write_data(command.parameters()).await?;
+        }
+        self.window = window;
+        Ok(())
+    }
+
+    /// Address `window` if needed, send RAMWR and leave the display selected in data mode
+    async fn start_frame(&mut self, window: Rect) -> Result<(), DisplayError> {
+        if window != self.window {
+            self.set_window(window).await?;
+        }
+        self.dc.set_low().map_err(pin_error)?; // Command mode
+        self.cs.set_low().map_err(pin_error)?; // Select the display
+        self.spi.write(&[Command::RamWr as u8]).await.map_err(bus_error)?;
+        self.spi.flush().await.map_err(bus_error)?;
+        self.dc.set_high().map_err(pin_error)?;
+        Ok(())
+    }
+
+    /// Wait for the pixel data to leave the bus and deselect the display
+    async fn end_frame(&mut self) -> Result<(), DisplayError> {
+        self.spi.flush().await.map_err(bus_error)?;
+        self.cs.set_high().map_err(pin_error)
+    }
+
+    /// Send `command` with its parameters and wait as long as it asks for
+    async fn send_command<DELAY: DelayNs>(&mut self, delay: &mut DELAY, command: &InitCommand) -> Result<(), DisplayError> {
+        self.write_command(command.command).await?;
+        if !command.parameters().is_empty() {
+            self.write_data(command.parameters()).await?;
+        }
+        if command.delay_ms > 0 {
+            delay.delay_ms(command.delay_ms).await;
+        }
+        Ok(())
+    }
+
+    /// Write a command to the display
+    async fn write_command(&mut self, command: u8) -> Result<(), DisplayError> {
+        self.dc.set_low().map_err(pin_error)?; // Command mode
+        self.cs.set_low().map_err(pin_error)?; // Select the display
+        self.spi.write(&[command]).await.map_err(bus_error)?;
+        self.spi.flush().await.map_err(bus_error)?;
+        self.cs.set_high().map_err(pin_error) // Deselect the display
+    }
+
+    /// Write data to the display
+    async fn write_data(&mut self, data: &[u8]) -> Result<(), DisplayError> {
+        self.dc.set_high().map_err(pin_error)?; // Data mode
+        self.cs.set_low().map_err(pin_error)?; // Select the display
+        self.spi.write(data).await.map_err(bus_error)?;
+        self.spi.flush().await.map_err(bus_error)?;
+        self.cs.set_high().map_err(pin_error) // Deselect the display
+    }
+}
@@ -0,0 +1,49 @@
+//! DMA abstraction used by the display driver
+//!
+//! The display driver hands a frame buffer and the SPI bus to a DMA channel and gets both back
+//! once the transfer has finished. Transfers that can chain further buffers behind the running
+//! one stream strips back to back. The firmware implements these traits on top of the RP2350 DMA;
+//! host tests implement them with mocks.
+
+/// A DMA channel that can stream a frame buffer to an SPI bus
+pub trait DmaChannel<SPI>: Sized {
+    type Transfer: DmaTransfer<SPI, Channel = Self>;
+
+    /// Start transmitting the first `len` bytes of `buffer` over `spi`
+    fn start(self, buffer: &'static mut [u8], len: usize, spi: SPI) -> Self::Transfer;
+
+    /// Clear the channel's pending completion interrupt, returning whether it was raised
+    fn ack_irq(&mut self) -> bool;
+}
+
+/// A running DMA transfer that owns the channel, the buffer and the SPI bus until it is done
+pub trait DmaTransfer<SPI> {
+    type Channel;
+
+    /// Whether all bytes have been handed to the SPI peripheral
+    fn is_done(&self) -> bool;
+
+    /// Clear the pending completion interrupt of the transfer's channel, returning whether it
+    /// was raised
+    fn ack_irq(&mut self) -> bool;
+
+    /// Queue the first `len` bytes of `buffer` to follow the running transfer without a gap,
+    /// e.g. on a second channel that the running one triggers when it ends
+    ///
+    /// Gives `buffer` back if the transfer cannot chain it, e.g. because a buffer is already
+    /// queued or the transfer has ended; the default never chains.
+    fn chain(&mut self, buffer: &'static mut [u8], len: usize) -> Result<(), &'static mut [u8]> {
+        let _ = len;
+        Err(buffer)
+    }
+
+    /// Take back the buffer ahead of a chained one once it has been sent
+    ///
+    /// `wait` only returns the last buffer, so take the earlier ones back first.
+    fn take_sent(&mut self) -> Option<&'static mut [u8]> {
+        None
+    }
+
+    /// Block until the transfer has finished and release its resources
+    fn wait(self) -> (Self::Channel, &'static mut [u8], SPI);
+}
@@ -0,0 +1,145 @@
+//! Frame buffer layout
+//!
+//! A frame is stored row by row, top to bottom, in the pixel format of the display
+//! and in the order in which the display expects the bytes after a RAMWR command.
+
+use core::ops::Range;
+
+use crate::color::PixelFormat;
+
+/// Native size of the Waveshare Pico LCD 2; other panels report theirs through `PanelController`
+pub const WIDTH: u16 = 240;
+pub const HEIGHT: u16 = 320;
+
+/// Size in bytes of a full-screen Pico LCD 2 frame buffer in the given pixel format
+pub const fn buffer_size(format: PixelFormat) -> usize {
+    format.bytes_for(WIDTH as usize * HEIGHT as usize)
+}
+
+/// Byte range of `rows` in a frame buffer that is `width` pixels wide
+pub fn row_bytes(format: PixelFormat, width: usize, rows: Range<usize>) -> Range<usize> {
+    let stride = format.bytes_for(width);
+    rows.start * stride..rows.end * stride
+}
+
+/// A rectangle of pixels, e.g. the part of a frame that changed
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Rect {
+    pub x: u16,
+    pub y: u16,
+    pub width: u16,
+    pub height: u16,
+}
+
+impl Rect {
+    pub const fn new(x: u16, y: u16, width: u16, height: u16) -> Self {
+        Self { x, y, width, height }
+    }
+
+    /// The whole of a `width` x `height` frame
+    pub const fn full(width: u16, height: u16) -> Self {
+        Self::new(0, 0, width, height)
+    }
+
+    pub const fn is_empty(&self) -> bool {
+        self.width == 0 || self.height == 0
+    }
+
+    /// Number of pixels covered
+    pub const fn area(&self) -> usize {
+        self.width as usize * self.height as usize
+    }
+
+    /// First column to the right of the rectangle, at most `u16::MAX`
+    pub const fn right(&self) -> u16 {
+        self.x.saturating_add(self.width)
+    }
+
+    /// First row below the rectangle, at most `u16::MAX`
+    pub const fn bottom(&self) -> u16 {
+        self.y.saturating_add(self.height)
+    }
+
+    /// Whether the rectangle lies within a `width` x `height` frame
+    pub const fn fits(&self, width: u16, height: u16) -> bool {
+        self.x as u32 + self.width as u32 <= width as u32 && self.y as u32 + self.height as u32 <= height as u32
+    }
+
+    /// Smallest rectangle covering both
+    pub fn union(&self, other: &Rect) -> Rect {
+        if self.is_empty() {
+            return *other;
+        }
+        if other.is_empty() {
+            return *self;
+        }
+        let x = self.x.min(other.x);
+        let y = self.y.min(other.y);
+        Rect::new(x, y, self.right().max(other.right()) - x, self.bottom().max(other.bottom()) - y)
+    }
+
+    /// Grow the rectangle until its rows start and end on whole bytes in `format`
+    ///
+    /// Only RGB444 packs two pixels into three bytes, so its columns are widened to even bounds.
+    pub fn aligned(&self, format: PixelFormat) -> Rect {
+        match format {
+            PixelFormat::Rgb444 => {
+                let x = self.x & !1;
+                Rect::new(x, self.y, (self.right().saturating_add(1) & !1) - x, self.height)
+            }
+            _ => *self,
+        }
+    }
+}
+
+/// Copy the pixels of `rect` out of a frame that is `width` pixels wide into `out`, row by row
+///
+/// `rect` has to be aligned to `format`. Returns the number of bytes written.
+pub fn pack_region(format: PixelFormat, frame: &[u8], width: usize, rect: Rect, out: &mut [u8]) -> usize {
+    let stride = format.bytes_for(width);
+    let offset = format.bytes_for(rect.x as usize);
+    let len = format.bytes_for(rect.width as usize);
+    if rect.is_empty() {
+        return 0;
+    }
+    let total = len * rect.height as usize;
+    for (row, chunk) in (rect.y as usize..).zip(out[..total].chunks_exact_mut(len)) {
+        let start = row * stride + offset;
+        chunk.copy_from_slice(&frame[start..start + len]);
+    }
+    total
+}
+
+/// Bounding box of everything drawn since the last update
+///
+/// Mark the areas an effect or overlay redraws, then `take` the region to send with
+/// `swap_region` and start over.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct DirtyRegion {
+    bounds: Option<Rect>,
+}
+
+impl DirtyRegion {
+    pub const fn new() -> Self {
+        Self { bounds: None }
+    }
+
+    pub fn mark(&mut self, rect: Rect) {
+        if rect.is_empty() {
+            return;
+        }
+        self.bounds = Some(match self.bounds {
+            Some(bounds) => bounds.union(&rect),
+            None => rect,
+        });
+    }
+
+    pub fn is_clean(&self) -> bool {
+        self.bounds.is_none()
+    }
+
+    /// The region to update, aligned to `format`, leaving the tracker clean
+    pub fn take(&mut self, format: PixelFormat) -> Option<Rect> {
+        self.bounds.take().map(|bounds| bounds.aligned(format))
+    }
+}
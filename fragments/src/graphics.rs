@@ -0,0 +1,137 @@
+//! embedded-graphics drawing onto frame buffers
+//!
+//! `Framebuffer` wraps a buffer handed out by the display driver, so that text, shapes and
+//! images from the embedded-graphics ecosystem can be drawn over the shader output before the
+//! buffer is swapped. The color type selects the pixel format the buffer is encoded in:
+//! `Rgb565` and `Rgb666` for their namesakes and, as embedded-graphics has no 12-bit color,
+//! `Rgb888` reduced to four bits per channel for RGB444.
+
+use core::convert::Infallible;
+use core::marker::PhantomData;
+use core::ops::Range;
+
+use embedded_graphics_core::pixelcolor::{IntoStorage, Rgb565, Rgb666, Rgb888, RgbColor};
+use embedded_graphics_core::prelude::{DrawTarget, OriginDimensions, PixelColor, Size};
+use embedded_graphics_core::Pixel;
+
+use crate::color::PixelFormat;
+
+/// embedded-graphics color type that matches one of the panel's pixel formats
+pub trait FrameColor: PixelColor {
+    const FORMAT: PixelFormat;
+
+    /// Encode the color as pixel number `pixel` of `bytes`
+    fn store(self, bytes: &mut [u8], pixel: usize);
+}
+
+impl FrameColor for Rgb888 {
+    const FORMAT: PixelFormat = PixelFormat::Rgb444;
+
+    fn store(self, bytes: &mut [u8], pixel: usize) {
+        let (r, g, b) = (self.r() >> 4, self.g() >> 4, self.b() >> 4);
+        // Two pixels share three bytes: RG B|R GB
+        let pair = &mut bytes[3 * (pixel / 2)..3 * (pixel / 2) + 3];
+        if pixel.is_multiple_of(2) {
+            pair[0] = (r << 4) | g;
+            pair[1] = (b << 4) | (pair[1] & 0x0F);
+        } else {
+            pair[1] = (pair[1] & 0xF0) | r;
+            pair[2] = (g << 4) | b;
+        }
+    }
+}
+
+impl FrameColor for Rgb565 {
+    const FORMAT: PixelFormat = PixelFormat::Rgb565;
+
+    fn store(self, bytes: &mut [u8], pixel: usize) {
+        bytes[2 * pixel..2 * pixel + 2].copy_from_slice(&self.into_storage().to_be_bytes());
+    }
+}
+
+impl FrameColor for Rgb666 {
+    const FORMAT: PixelFormat = PixelFormat::Rgb666;
+
+    fn store(self, bytes: &mut [u8], pixel: usize) {
+        bytes[3 * pixel..3 * pixel + 3].copy_from_slice(&[self.r() << 2, self.g() << 2, self.b() << 2]);
+    }
+}
+
+/// Draw target on a frame buffer, or on a strip of rows of a frame when streaming
+///
+/// Coordinates are frame coordinates; pixels outside the frame or the rows held by the buffer
+/// are skipped, so the same drawing can be repeated for every strip.
+pub struct Framebuffer<'a, C: FrameColor> {
+    bytes: &'a mut [u8],
+    width: u16,
+    height: u16,
+    rows: Range<u16>,
+    color: PhantomData<C>,
+}
+
+impl<'a, C: FrameColor> Framebuffer<'a, C> {
+    /// Draw onto a full `width` x `height` frame in `bytes`
+    ///
+    /// Returns `None` if `bytes` cannot hold the frame.
+    pub fn new(bytes: &'a mut [u8], width: u16, height: u16) -> Option<Self> {
+        if bytes.len() < C::FORMAT.bytes_for(width as usize * height as usize) {
+            return None;
+        }
+        Self::strip(bytes, width, height, 0)
+    }
+
+    /// Draw onto the rows of a `width` x `height` frame that `bytes` holds from `first_row` on
+    ///
+    /// Returns `None` if the rows cannot be encoded on their own, i.e. for an odd width in
+    /// RGB444.
+    pub fn strip(bytes: &'a mut [u8], width: u16, height: u16, first_row: u16) -> Option<Self> {
+        if C::FORMAT == PixelFormat::Rgb444 && !width.is_multiple_of(2) {
+            return None;
+        }
+        let stride = C::FORMAT.bytes_for(width as usize).max(1);
+        let rows = (bytes.len() / stride).min(height.saturating_sub(first_row) as usize) as u16;
+        Some(Self { bytes, width, height, rows: first_row..first_row + rows, color: PhantomData })
+    }
+
+    /// Frame rows held by the buffer
+    pub fn rows(&self) -> Range<u16> {
+        self.rows.clone()
+    }
+}
+
+impl<C: FrameColor> OriginDimensions for Framebuffer<'_, C> {
+    fn size(&self) -> Size {
+        Size::new(self.width as u32, self.height as u32)
+    }
+}
+
+impl<C: FrameColor> DrawTarget for Framebuffer<'_, C> {
+    type Color = C;
+    type Error = Infallible;
+
+    fn draw_iter<I: IntoIterator<Item = Pixel<C>>>(&mut self, pixels: I) -> Result<(), Infallible> {
+        for Pixel(point, color) in pixels {
+            let (Ok(x), Ok(y)) = (u16::try_from(point.x), u16::try_from(point.y)) else {
+                continue;
+            };
+            if x < self.width && self.rows.contains(&y) {
+                let pixel = (y - self.rows.start) as usize * self.width as usize + x as usize;
+                color.store(self.bytes, pixel);
+            }
+        }
+        Ok(())
+    }
+
+    fn clear(&mut self, color: C) -> Result<(), Infallible> {
+        // Encode a pair of pixels, the smallest whole number of bytes in every format
+        let mut pattern = [0; 6];
+        color.store(&mut pattern, 0);
+        color.store(&mut pattern, 1);
+        let pattern = &pattern[..C::FORMAT.bytes_for(2)];
+        let len = C::FORMAT.bytes_for(self.rows.len() * self.width as usize);
+        for chunk in self.bytes[..len].chunks_mut(pattern.len()) {
+            chunk.copy_from_slice(&pattern[..chunk.len()]);
+        }
+        Ok(())
+    }
+}
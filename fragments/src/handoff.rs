@@ -0,0 +1,268 @@
+//! Handing rows of a frame buffer to the other core
+//!
+//! `Handoff` lends a `Strip` of a frame buffer and a copy of per-frame data such as the
+//! uniforms to whichever core `serve`s it, and blocks the lending core until that work is
+//! done. The borrow of the strip thus lasts for the whole exchange, like with a scoped
+//! thread, and the compiler checks that the strip is disjoint from the rows the lending core
+//! renders meanwhile.
+//!
+//! With `share`, the strip is instead cut into bands of rows that both cores take from a
+//! shared atomic counter until none are left, so the core that gets the cheaper rows of a
+//! frame helps out with the rest instead of idling.
+//!
+//! A serving core that panics or hangs would keep the lending core waiting forever. With
+//! `with_recovery`, the lending core checks a hook while it waits, which may stop the serving
+//! core for good and so end the exchange.
+
+use core::cell::UnsafeCell;
+use core::ops::Range;
+use core::sync::atomic::{AtomicU8, AtomicUsize, Ordering};
+
+/// Consecutive rows of a frame buffer, starting with frame row `first_row`
+pub struct Strip<'a> {
+    pub bytes: &'a mut [u8],
+    pub first_row: usize,
+}
+
+impl<'a> Strip<'a> {
+    pub fn new(bytes: &'a mut [u8], first_row: usize) -> Self {
+        Self { bytes, first_row }
+    }
+
+    /// Split into the first `rows` rows of `stride` bytes and the remaining ones
+    ///
+    /// # Panics
+    ///
+    /// If `stride` is 0.
+    pub fn split_at_row(self, rows: usize, stride: usize) -> (Strip<'a>, Strip<'a>) {
+        assert!(stride > 0, "empty rows");
+        let (top, bottom) = self.bytes.split_at_mut((rows * stride).min(self.bytes.len()));
+        let bottom_row = self.first_row + top.len() / stride;
+        (Strip::new(top, self.first_row), Strip::new(bottom, bottom_row))
+    }
+
+    /// Frame rows held, for rows of `stride` bytes
+    ///
+    /// # Panics
+    ///
+    /// If `stride` is 0.
+    pub fn rows(&self, stride: usize) -> Range<usize> {
+        assert!(stride > 0, "empty rows");
+        self.first_row..self.first_row + self.bytes.len() / stride
+    }
+}
+
+const EMPTY: u8 = 0;
+const WRITING: u8 = 1;
+const READY: u8 = 2;
+const BUSY: u8 = 3;
+const DONE: u8 = 4;
+
+struct Job<T> {
+    bytes: *mut u8,
+    len: usize,
+    first_row: usize,
+    /// Each band but the last holds `band_rows` rows in `band_len` bytes
+    band_len: usize,
+    band_rows: usize,
+    data: T,
+}
+
+impl<T> Job<T> {
+    fn bands(&self) -> usize {
+        self.len.div_ceil(self.band_len)
+    }
+}
+
+/// Single-slot exchange of work between two cores
+///
+/// Only one exchange runs at a time, so one `Handoff` serves one lending and one serving
+/// core. While waiting, each side calls the `wait` function given to `with_signal`, and
+/// `notify` after every change the other side may be waiting for, e.g. `wfe` and `sev` on
+/// Cortex-M. `wait` has to return now and then for a recovery hook to be checked, e.g. on a
+/// periodic interrupt.
+pub struct Handoff<T> {
+    state: AtomicU8,
+    job: UnsafeCell<Option<Job<T>>>,
+    /// Next band of the job to take
+    next_band: AtomicUsize,
+    wait: fn(),
+    notify: fn(),
+    /// Whether the serving core has been stopped, checked by the lending side while waiting
+    reclaim: fn() -> bool,
+}
+
+// The job is only accessed by the side that owns the current state: the lending side from
+// EMPTY until READY and after DONE or the serving side being stopped, the serving side from
+// BUSY until DONE. Both read the data while sharing bands.
+unsafe impl<T: Send + Sync> Sync for Handoff<T> {}
+
+impl<T: Send> Handoff<T> {
+    /// Handoff that busy-waits
+    pub const fn new() -> Self {
+        Self::with_signal(core::hint::spin_loop, || {})
+    }
+
+    /// Handoff that waits with `wait` and wakes the other side with `notify`
+    pub const fn with_signal(wait: fn(), notify: fn()) -> Self {
+        // SAFETY: the lending side never gives up on the serving core
+        unsafe { Self::with_recovery(wait, notify, || false) }
+    }
+
+    /// Handoff like `with_signal` that calls `reclaim` whenever the lending side waits for the
+    /// serving core, and ends the exchange once it returns `true`
+    ///
+    /// The lending side then takes the strip back, with the serving core's part of it possibly
+    /// not or only partly done. `reclaim` is where a core that failed to make progress is
+    /// stopped, and keeps returning `true` while it is off.
+    ///
+    /// # Safety
+    ///
+    /// `reclaim` may only return `true` once the serving core no longer runs and never
+    /// resumes the exchange it was in, e.g. because it has been held in reset. It may be
+    /// started again to serve later exchanges.
+    pub const unsafe fn with_recovery(wait: fn(), notify: fn(), reclaim: fn() -> bool) -> Self {
+        Self {
+            state: AtomicU8::new(EMPTY),
+            job: UnsafeCell::new(None),
+            next_band: AtomicUsize::new(0),
+            wait,
+            notify,
+            reclaim,
+        }
+    }
+
+    /// Lend `strip` and `data` to the serving core and run `local` meanwhile
+    ///
+    /// Returns the result of `local` once the serving core has finished with the strip or has
+    /// been stopped by the recovery hook, also waiting for it if `local` panics.
+    ///
+    /// # Panics
+    ///
+    /// If another exchange is in progress, i.e. two cores lend at the same time.
+    pub fn run<R>(&self, strip: Strip<'_>, data: T, local: impl FnOnce() -> R) -> R {
+        let band_len = strip.bytes.len().max(1);
+        self.publish(strip, band_len, 0, data);
+        let _returned = Returned(self);
+        local()
+    }
+
+    /// Share the bands of `band_rows` rows of `stride` bytes in `strip` with the serving core
+    ///
+    /// Both cores call their `work` on one band at a time until all bands are done. The
+    /// serving core may get none if it only shows up once this core is through.
+    ///
+    /// # Panics
+    ///
+    /// If another exchange is in progress, or `band_rows` or `stride` is 0.
+    pub fn share(&self, strip: Strip<'_>, band_rows: usize, stride: usize, data: T, mut work: impl FnMut(Strip<'_>, &T)) {
+        assert!(band_rows > 0 && stride > 0, "empty bands");
+        self.publish(strip, band_rows * stride, band_rows, data);
+        let _returned = Returned(self);
+        // SAFETY: the lending side only reads the job, which stays in place until `Returned`
+        let job = unsafe { (*self.job.get()).as_ref().unwrap() };
+        while let Some(band) = self.take_band(job) {
+            work(band, &job.data);
+        }
+    }
+
+    /// Wait for work lent with `run` or `share` and do it
+    ///
+    /// `work` is called for every band taken, or once for a non-empty strip lent with `run`.
+    /// The strip is released to the lending core when the last call returns or one panics.
+    pub fn serve(&self, mut work: impl FnMut(Strip<'_>, &T)) {
+        while self.state.compare_exchange(READY, BUSY, Ordering::Acquire, Ordering::Relaxed).is_err() {
+            (self.wait)();
+        }
+        let _done = Done(self);
+        // SAFETY: BUSY keeps the lending side from clearing the job until `Done` is dropped,
+        // and the lending side keeps the strip borrowed until it sees DONE
+        let job = unsafe { (*self.job.get()).as_ref().unwrap() };
+        while let Some(band) = self.take_band(job) {
+            work(band, &job.data);
+        }
+    }
+
+    fn publish(&self, strip: Strip<'_>, band_len: usize, band_rows: usize, data: T) {
+        if self.state.compare_exchange(EMPTY, WRITING, Ordering::Acquire, Ordering::Relaxed).is_err() {
+            panic!("handoff already in use");
+        }
+        let job = Job {
+            bytes: strip.bytes.as_mut_ptr(),
+            len: strip.bytes.len(),
+            first_row: strip.first_row,
+            band_len,
+            band_rows,
+            data,
+        };
+        // SAFETY: WRITING keeps the serving side away from the job
+        unsafe { *self.job.get() = Some(job) };
+        self.next_band.store(0, Ordering::Relaxed);
+        self.state.store(READY, Ordering::Release);
+        (self.notify)();
+    }
+
+    /// Claim the next band of `job` that no core has taken yet
+    fn take_band<'a>(&self, job: &'a Job<T>) -> Option<Strip<'a>> {
+        let band = self.next_band.fetch_add(1, Ordering::Relaxed);
+        if band >= job.bands() {
+            return None;
+        }
+        let start = band * job.band_len;
+        let len = job.band_len.min(job.len - start);
+        // SAFETY: every band index is handed out once, so the bands are disjoint parts of the
+        // lent strip, which stays borrowed until the exchange ends
+        let bytes = unsafe { core::slice::from_raw_parts_mut(job.bytes.add(start), len) };
+        Some(Strip::new(bytes, job.first_row + band * job.band_rows))
+    }
+}
+
+impl<T: Send> Default for Handoff<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Ends the lending side of an exchange: waits for the strip to come back and clears the job
+struct Returned<'a, T>(&'a Handoff<T>);
+
+impl<T> Drop for Returned<'_, T> {
+    fn drop(&mut self) {
+        let handoff = self.0;
+        loop {
+            match handoff.state.load(Ordering::Acquire) {
+                DONE => break,
+                // Nothing left for the serving core, so stop waiting for it to show up
+                READY if handoff.all_bands_taken() => {
+                    if handoff.state.compare_exchange(READY, DONE, Ordering::Acquire, Ordering::Relaxed).is_ok() {
+                        break;
+                    }
+                }
+                // The serving core is off, so the job is ours again
+                _ if (handoff.reclaim)() => break,
+                _ => (handoff.wait)(),
+            }
+        }
+        // SAFETY: DONE or a stopped serving core hands the job back to the lending side
+        unsafe { *handoff.job.get() = None };
+        handoff.state.store(EMPTY, Ordering::Release);
+    }
+}
+
+impl<T> Handoff<T> {
+    fn all_bands_taken(&self) -> bool {
+        // SAFETY: only called by the lending side, for which the job stays in place
+        let bands = unsafe { (*self.job.get()).as_ref().map_or(0, Job::bands) };
+        self.next_band.load(Ordering::Relaxed) >= bands
+    }
+}
+
+/// Ends the serving side of an exchange
+struct Done<'a, T>(&'a Handoff<T>);
+
+impl<T> Drop for Done<'_, T> {
+    fn drop(&mut self) {
+        self.0.state.store(DONE, Ordering::Release);
+        (self.0.notify)();
+    }
+}
@@ -0,0 +1,28 @@
+//! Hardware-independent rendering core of rusty-pico-fragments
+//!
+//! Everything needed to turn a fragment shader into frame buffer contents lives here,
+//! so that shaders can be built and tested on the host as well as on the Pico 2.
+
+#![no_std]
+
+pub mod backlight;
+pub mod color;
+pub mod display;
+#[cfg(feature = "async")]
+pub mod display_async;
+pub mod dma;
+pub mod framebuffer;
+#[cfg(feature = "graphics")]
+pub mod graphics;
+pub mod handoff;
+pub mod panel;
+pub mod panel_config;
+pub mod pipeline;
+pub mod power;
+pub mod schedule;
+pub mod scroll;
+pub mod shader;
+pub mod shaders;
+pub mod supervisor;
+pub mod timing;
+pub mod uniforms;
@@ -0,0 +1,328 @@
+//! Panel controllers
+//!
+//! The display drivers only speak the MIPI DCS commands that the ST7789, ILI9341 and GC9A01
+//! share: reset, sleep out, display on, the address window, memory write, MADCTL and COLMOD.
+//! Everything that differs between controllers and the modules built around them is described
+//! by a `PanelController`: the visible size and where it sits in controller RAM, the accepted
+//! pixel formats, and the vendor commands of the power-up sequence.
+
+use crate::color::PixelFormat;
+use crate::display::Orientation;
+use crate::panel_config::{PanelConfig, CONFIG_COMMANDS};
+use crate::scroll::ScrollArea;
+
+/// Most parameters an `InitCommand` can carry
+pub const MAX_PARAMETERS: usize = 16;
+
+const MADCTL_MY: u8 = 0x80;
+const MADCTL_MX: u8 = 0x40;
+const MADCTL_MV: u8 = 0x20;
+const MADCTL_BGR: u8 = 0x08;
+
+/// A command of a panel's initialization sequence with its parameters
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct InitCommand {
+    pub command: u8,
+    parameters: [u8; MAX_PARAMETERS],
+    len: u8,
+    /// Time to wait after the command, in milliseconds
+    pub delay_ms: u32,
+}
+
+impl InitCommand {
+    /// Panics if there are more than `MAX_PARAMETERS` parameters, at compile time for constants
+    pub const fn new(command: u8, parameters: &[u8]) -> Self {
+        assert!(parameters.len() <= MAX_PARAMETERS, "too many parameters for an InitCommand");
+        let mut array = [0; MAX_PARAMETERS];
+        let mut i = 0;
+        while i < parameters.len() {
+            array[i] = parameters[i];
+            i += 1;
+        }
+        Self { command, parameters: array, len: parameters.len() as u8, delay_ms: 0 }
+    }
+
+    /// Wait `ms` milliseconds after sending the command
+    pub const fn with_delay(mut self, ms: u32) -> Self {
+        self.delay_ms = ms;
+        self
+    }
+
+    pub fn parameters(&self) -> &[u8] {
+        &self.parameters[..self.len as usize]
+    }
+}
+
+/// Everything the display drivers need to know about a panel and its controller
+pub trait PanelController {
+    /// Visible width and height in the panel's native orientation
+    fn size(&self) -> (u16, u16);
+
+    /// Width and height of the controller's frame memory in native orientation
+    fn ram_size(&self) -> (u16, u16) {
+        self.size()
+    }
+
+    /// Column and row of the visible area in frame memory, in native orientation
+    fn ram_offset(&self) -> (u16, u16) {
+        (0, 0)
+    }
+
+    /// COLMOD value that selects `format`, or `None` if the panel cannot be driven in it
+    fn colmod(&self, format: PixelFormat) -> Option<u8> {
+        Some(format.colmod())
+    }
+
+    /// MADCTL value for `orientation`, including the panel's RGB/BGR subpixel order
+    fn madctl(&self, orientation: Orientation) -> u8 {
+        orientation.madctl()
+    }
+
+    /// Vendor commands sent once sleep out, pixel format and orientation are set
+    fn init_sequence(&self) -> &[InitCommand];
+
+    /// Size in bytes of a full frame in the given pixel format
+    fn buffer_size(&self, format: PixelFormat) -> usize {
+        let (width, height) = self.size();
+        format.bytes_for(width as usize * height as usize)
+    }
+
+    /// Column and row address of the top left visible pixel in `orientation`
+    ///
+    /// Mirroring moves the visible area to the other end of frame memory, and exchanging rows
+    /// and columns swaps the offsets.
+    fn window_offset(&self, orientation: Orientation) -> (u16, u16) {
+        let madctl = self.madctl(orientation);
+        let (width, height) = self.size();
+        let (ram_width, ram_height) = self.ram_size();
+        let (x, y) = self.ram_offset();
+        let x = if madctl & MADCTL_MX != 0 { ram_width - x - width } else { x };
+        let y = if madctl & MADCTL_MY != 0 { ram_height - y - height } else { y };
+        if madctl & MADCTL_MV != 0 { (y, x) } else { (x, y) }
+    }
+
+    /// VSCRDEF rows of frame memory above, within and below the scroll region for `area` of
+    /// the image in `orientation`
+    ///
+    /// Panels scroll along their native rows, so this is `None` in landscape, and also if
+    /// `area` does not add up to the image height or has nothing to scroll. Rows outside the
+    /// visible area count as fixed.
+    fn scroll_definition(&self, orientation: Orientation, area: ScrollArea) -> Option<[u16; 3]> {
+        let madctl = self.madctl(orientation);
+        let height = self.size().1;
+        let rows = area.top_fixed as u32 + area.scroll_height as u32 + area.bottom_fixed as u32;
+        if madctl & MADCTL_MV != 0 || area.scroll_height == 0 || rows != height as u32 {
+            return None;
+        }
+        let (_, y) = self.window_offset(orientation);
+        let ram_height = self.ram_size().1;
+        // Mirrored rows put the top of the image at the end of frame memory
+        let top = if madctl & MADCTL_MY != 0 {
+            ram_height - y - area.top_fixed - area.scroll_height
+        } else {
+            y + area.top_fixed
+        };
+        Some([top, area.scroll_height, ram_height - top - area.scroll_height])
+    }
+
+    /// VSCSAD row of frame memory that shows the image scrolled up by `offset` rows within
+    /// `area`, defined with `scroll_definition`
+    fn scroll_start(&self, orientation: Orientation, area: ScrollArea, offset: u16) -> u16 {
+        let top = self.scroll_definition(orientation, area).map_or(0, |[top, _, _]| top);
+        let offset = offset % area.scroll_height.max(1);
+        if self.madctl(orientation) & MADCTL_MY != 0 {
+            top + (area.scroll_height - offset) % area.scroll_height
+        } else {
+            top + offset
+        }
+    }
+}
+
+/// Sitronix ST7789 modules, which show part or all of the controller's 240x320 frame memory
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct St7789 {
+    width: u16,
+    height: u16,
+    x_offset: u16,
+    y_offset: u16,
+    config: PanelConfig,
+    /// `config` as commands
+    init: [InitCommand; CONFIG_COMMANDS],
+}
+
+impl St7789 {
+    /// 2 inch 240x320 module, e.g. the Waveshare Pico LCD 2
+    pub const LCD_240X320: Self = Self::new(240, 320, 0, 0);
+    /// 1.3 inch 240x240 module, e.g. the Waveshare Pico LCD 1.3
+    pub const LCD_240X240: Self = Self::new(240, 240, 0, 0);
+    /// 1.14 inch 135x240 module, e.g. the Waveshare Pico LCD 1.14
+    pub const LCD_135X240: Self = Self::new(135, 240, 52, 40);
+
+    /// A module showing `width` x `height` pixels from column `x_offset` and row `y_offset`
+    /// of frame memory
+    pub const fn new(width: u16, height: u16, x_offset: u16, y_offset: u16) -> Self {
+        let config = PanelConfig::new();
+        Self { width, height, x_offset, y_offset, config, init: config.commands() }
+    }
+
+    /// Tune porches, frame rate, voltages and gamma; takes effect with the next `init`
+    pub const fn with_config(mut self, config: PanelConfig) -> Self {
+        self.config = config;
+        self.init = config.commands();
+        self
+    }
+
+    pub const fn config(&self) -> &PanelConfig {
+        &self.config
+    }
+}
+
+impl Default for St7789 {
+    fn default() -> Self {
+        Self::LCD_240X320
+    }
+}
+
+impl PanelController for St7789 {
+    fn size(&self) -> (u16, u16) {
+        (self.width, self.height)
+    }
+
+    fn ram_size(&self) -> (u16, u16) {
+        (240, 320)
+    }
+
+    fn ram_offset(&self) -> (u16, u16) {
+        (self.x_offset, self.y_offset)
+    }
+
+    /// RGB444 packs two pixels into three bytes, so rows of an odd width would split a byte
+    fn colmod(&self, format: PixelFormat) -> Option<u8> {
+        match format {
+            PixelFormat::Rgb444 if !self.width.is_multiple_of(2) => None,
+            _ => Some(format.colmod()),
+        }
+    }
+
+    fn init_sequence(&self) -> &[InitCommand] {
+        &self.init
+    }
+}
+
+/// Ilitek ILI9341 240x320 panel, usually mounted as 320x240 in landscape
+///
+/// Its serial interface has no 12-bit mode, and the panels wire their subpixels in BGR order.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Ili9341;
+
+const ILI9341_INIT: &[InitCommand] = &[
+    InitCommand::new(0xEF, &[0x03, 0x80, 0x02]),
+    InitCommand::new(0xCF, &[0x00, 0xC1, 0x30]), // Power control B
+    InitCommand::new(0xED, &[0x64, 0x03, 0x12, 0x81]), // Power on sequence control
+    InitCommand::new(0xE8, &[0x85, 0x00, 0x78]), // Driver timing control A
+    InitCommand::new(0xCB, &[0x39, 0x2C, 0x00, 0x34, 0x02]), // Power control A
+    InitCommand::new(0xF7, &[0x20]), // Pump ratio control
+    InitCommand::new(0xEA, &[0x00, 0x00]), // Driver timing control B
+    InitCommand::new(0xC0, &[0x23]), // Power control 1
+    InitCommand::new(0xC1, &[0x10]), // Power control 2
+    InitCommand::new(0xC5, &[0x3E, 0x28]), // VCOM control 1
+    InitCommand::new(0xC7, &[0x86]), // VCOM control 2
+    InitCommand::new(0xB1, &[0x00, 0x18]), // Frame rate control, 79 Hz
+    InitCommand::new(0xB6, &[0x08, 0x82, 0x27]), // Display function control
+    InitCommand::new(0xF2, &[0x00]), // 3-gamma off
+    InitCommand::new(0x26, &[0x01]), // Gamma curve 1
+    InitCommand::new(0xE0, &[0x0F, 0x31, 0x2B, 0x0C, 0x0E, 0x08, 0x4E, 0xF1, 0x37, 0x07, 0x10, 0x03, 0x0E, 0x09, 0x00]),
+    InitCommand::new(0xE1, &[0x00, 0x0E, 0x14, 0x03, 0x11, 0x07, 0x31, 0xC1, 0x48, 0x08, 0x0F, 0x0C, 0x31, 0x36, 0x0F]),
+];
+
+impl PanelController for Ili9341 {
+    fn size(&self) -> (u16, u16) {
+        (240, 320)
+    }
+
+    fn colmod(&self, format: PixelFormat) -> Option<u8> {
+        match format {
+            PixelFormat::Rgb444 => None,
+            PixelFormat::Rgb565 => Some(0x55),
+            PixelFormat::Rgb666 => Some(0x66),
+        }
+    }
+
+    fn madctl(&self, orientation: Orientation) -> u8 {
+        orientation.madctl() | MADCTL_BGR
+    }
+
+    fn init_sequence(&self) -> &[InitCommand] {
+        ILI9341_INIT
+    }
+}
+
+/// Galaxycore GC9A01 round 240x240 panel
+///
+/// The corners of the frame lie outside the circular glass and are simply not visible.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Gc9a01;
+
+const GC9A01_INIT: &[InitCommand] = &[
+    // Unlock the vendor command set
+    InitCommand::new(0xEF, &[]),
+    InitCommand::new(0xEB, &[0x14]),
+    InitCommand::new(0xFE, &[]),
+    InitCommand::new(0xEF, &[]),
+    InitCommand::new(0xEB, &[0x14]),
+    InitCommand::new(0x84, &[0x40]),
+    InitCommand::new(0x85, &[0xFF]),
+    InitCommand::new(0x86, &[0xFF]),
+    InitCommand::new(0x87, &[0xFF]),
+    InitCommand::new(0x88, &[0x0A]),
+    InitCommand::new(0x89, &[0x21]),
+    InitCommand::new(0x8A, &[0x00]),
+    InitCommand::new(0x8B, &[0x80]),
+    InitCommand::new(0x8C, &[0x01]),
+    InitCommand::new(0x8D, &[0x01]),
+    InitCommand::new(0x8E, &[0xFF]),
+    InitCommand::new(0x8F, &[0xFF]),
+    InitCommand::new(0xB6, &[0x00, 0x20]), // Display function control
+    InitCommand::new(0x90, &[0x08, 0x08, 0x08, 0x08]),
+    InitCommand::new(0xBD, &[0x06]),
+    InitCommand::new(0xBC, &[0x00]),
+    InitCommand::new(0xFF, &[0x60, 0x01, 0x04]),
+    InitCommand::new(0xC3, &[0x13]), // Power control 2
+    InitCommand::new(0xC4, &[0x13]), // Power control 3
+    InitCommand::new(0xC9, &[0x22]), // Power control 4
+    InitCommand::new(0xBE, &[0x11]),
+    InitCommand::new(0xE1, &[0x10, 0x0E]),
+    InitCommand::new(0xDF, &[0x21, 0x0C, 0x02]),
+    InitCommand::new(0xF0, &[0x45, 0x09, 0x08, 0x08, 0x26, 0x2A]), // Gamma 1
+    InitCommand::new(0xF1, &[0x43, 0x70, 0x72, 0x36, 0x37, 0x6F]), // Gamma 2
+    InitCommand::new(0xF2, &[0x45, 0x09, 0x08, 0x08, 0x26, 0x2A]), // Gamma 3
+    InitCommand::new(0xF3, &[0x43, 0x70, 0x72, 0x36, 0x37, 0x6F]), // Gamma 4
+    InitCommand::new(0xED, &[0x1B, 0x0B]),
+    InitCommand::new(0xAE, &[0x77]),
+    InitCommand::new(0xCD, &[0x63]),
+    InitCommand::new(0x70, &[0x07, 0x07, 0x04, 0x0E, 0x0F, 0x09, 0x07, 0x08, 0x03]),
+    InitCommand::new(0xE8, &[0x34]), // Frame rate
+    InitCommand::new(0x62, &[0x18, 0x0D, 0x71, 0xED, 0x70, 0x70, 0x18, 0x0F, 0x71, 0xEF, 0x70, 0x70]),
+    InitCommand::new(0x63, &[0x18, 0x11, 0x71, 0xF1, 0x70, 0x70, 0x18, 0x13, 0x71, 0xF3, 0x70, 0x70]),
+    InitCommand::new(0x64, &[0x28, 0x29, 0xF1, 0x01, 0xF1, 0x00, 0x07]),
+    InitCommand::new(0x66, &[0x3C, 0x00, 0xCD, 0x67, 0x45, 0x45, 0x10, 0x00, 0x00, 0x00]),
+    InitCommand::new(0x67, &[0x00, 0x3C, 0x00, 0x00, 0x00, 0x01, 0x54, 0x10, 0x32, 0x98]),
+    InitCommand::new(0x74, &[0x10, 0x85, 0x80, 0x00, 0x00, 0x4E, 0x00]),
+    InitCommand::new(0x98, &[0x3E, 0x07]),
+    InitCommand::new(0x35, &[]), // Tearing effect line on
+    InitCommand::new(0x21, &[]), // Display inversion on
+];
+
+impl PanelController for Gc9a01 {
+    fn size(&self) -> (u16, u16) {
+        (240, 240)
+    }
+
+    fn madctl(&self, orientation: Orientation) -> u8 {
+        orientation.madctl() | MADCTL_BGR
+    }
+
+    fn init_sequence(&self) -> &[InitCommand] {
+        GC9A01_INIT
+    }
+}
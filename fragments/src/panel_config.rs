@@ -0,0 +1,395 @@
+//! ST7789 panel tuning
+//!
+//! Porch, frame rate, voltage and gamma registers of the ST7789 as typed values. The defaults
+//! are the controller's reset values, with the gamma curves of the Waveshare Pico LCD modules;
+//! change them to match the refresh rate to the render rate or to adjust a batch of panels.
+
+use crate::panel::InitCommand;
+
+/// ST7789VW tuning commands
+#[repr(u8)]
+enum Command {
+    InvOff = 0x20,
+    InvOn = 0x21,
+    PorCtrl = 0xB2,
+    GCtrl = 0xB7,
+    VcomS = 0xBB,
+    LcmCtrl = 0xC0,
+    VdvVrhEn = 0xC2,
+    VrhSet = 0xC3,
+    VdvSet = 0xC4,
+    FrCtrl2 = 0xC6,
+    PwCtrl1 = 0xD0,
+    PvGamCtrl = 0xE0,
+    NvGamCtrl = 0xE1,
+}
+
+/// Number of commands `PanelConfig` sends
+pub const CONFIG_COMMANDS: usize = 12;
+
+/// Gate driver high voltage VGH
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Vgh {
+    V12_20,
+    V12_54,
+    V12_89,
+    V13_26,
+    V13_65,
+    V14_06,
+    V14_50,
+    V14_97,
+}
+
+/// Gate driver low voltage VGL
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Vgl {
+    Minus7_16,
+    Minus7_67,
+    Minus8_23,
+    Minus8_87,
+    Minus9_60,
+    Minus10_43,
+    Minus11_38,
+    Minus12_50,
+}
+
+/// Analog supply voltage AVDD
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Avdd {
+    V6_4,
+    V6_6,
+    V6_8,
+}
+
+/// Negative analog supply voltage AVCL
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Avcl {
+    Minus4_4,
+    Minus4_6,
+    Minus4_8,
+    Minus5_0,
+}
+
+/// Source driver supply voltage VDS
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Vds {
+    V2_19,
+    V2_30,
+    V2_40,
+    V2_51,
+}
+
+/// How the source drivers alternate polarity between frames
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum InversionMode {
+    /// Alternate per pixel, the least visible flicker
+    Dot,
+    /// Alternate per column, using less power
+    Column,
+}
+
+/// LCMCTRL: settings XORed with the MADCTL bits, to match how a module wires up the glass
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct LcmControl {
+    /// Flip MADCTL MY
+    pub xmy: bool,
+    /// Flip the RGB/BGR order
+    pub xbgr: bool,
+    /// Flip display inversion
+    pub xinv: bool,
+    /// Flip MADCTL MX
+    pub xmx: bool,
+    /// Flip MADCTL MH
+    pub xmh: bool,
+    /// Flip MADCTL MV
+    pub xmv: bool,
+    /// Reverse the gate scan direction
+    pub xgs: bool,
+}
+
+impl LcmControl {
+    /// Reset value 0x2C
+    pub const DEFAULT: Self = Self { xmy: false, xbgr: true, xinv: false, xmx: true, xmh: true, xmv: false, xgs: false };
+
+    pub const fn bits(self) -> u8 {
+        (self.xmy as u8) << 6
+            | (self.xbgr as u8) << 5
+            | (self.xinv as u8) << 4
+            | (self.xmx as u8) << 3
+            | (self.xmh as u8) << 2
+            | (self.xmv as u8) << 1
+            | self.xgs as u8
+    }
+}
+
+impl Default for LcmControl {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
+
+/// One gamma curve of PVGAMCTRL or NVGAMCTRL
+///
+/// `vN` sets the voltage of gray level N out of 0 to 63; the levels in between are
+/// interpolated. `j0` and `j1` fine-tune the interpolation in the dark and bright range.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Gamma {
+    pub v0: u8,
+    pub v1: u8,
+    pub v2: u8,
+    pub v4: u8,
+    pub v6: u8,
+    pub v13: u8,
+    pub v20: u8,
+    pub v27: u8,
+    pub v36: u8,
+    pub v43: u8,
+    pub v50: u8,
+    pub v57: u8,
+    pub v59: u8,
+    pub v61: u8,
+    pub v62: u8,
+    pub v63: u8,
+    pub j0: u8,
+    pub j1: u8,
+}
+
+impl Gamma {
+    /// Positive polarity curve of the Waveshare Pico LCD modules
+    pub const POSITIVE: Self = Self::from_bytes([0xD0, 0x08, 0x11, 0x08, 0x0C, 0x15, 0x39, 0x33, 0x50, 0x36, 0x13, 0x14, 0x29, 0x2D]);
+    /// Negative polarity curve of the Waveshare Pico LCD modules
+    pub const NEGATIVE: Self = Self::from_bytes([0xD0, 0x08, 0x10, 0x08, 0x06, 0x06, 0x39, 0x44, 0x51, 0x0B, 0x16, 0x14, 0x2F, 0x31]);
+
+    /// Decode the 14 parameter bytes of a gamma command, e.g. from a panel vendor's init code
+    pub const fn from_bytes(b: [u8; 14]) -> Self {
+        Self {
+            v63: b[0] >> 4,
+            v0: b[0] & 0x0F,
+            v1: b[1] & 0x3F,
+            v2: b[2] & 0x3F,
+            v4: b[3] & 0x1F,
+            v6: b[4] & 0x1F,
+            j0: (b[5] >> 4) & 0x03,
+            v13: b[5] & 0x0F,
+            v20: b[6] & 0x7F,
+            v36: (b[7] >> 4) & 0x07,
+            v27: b[7] & 0x07,
+            v43: b[8] & 0x7F,
+            j1: (b[9] >> 4) & 0x03,
+            v50: b[9] & 0x0F,
+            v57: b[10] & 0x1F,
+            v59: b[11] & 0x1F,
+            v61: b[12] & 0x3F,
+            v62: b[13] & 0x3F,
+        }
+    }
+
+    /// Encode as the 14 parameter bytes of a gamma command, dropping bits out of range
+    pub const fn to_bytes(self) -> [u8; 14] {
+        [
+            (self.v63 & 0x0F) << 4 | (self.v0 & 0x0F),
+            self.v1 & 0x3F,
+            self.v2 & 0x3F,
+            self.v4 & 0x1F,
+            self.v6 & 0x1F,
+            (self.j0 & 0x03) << 4 | (self.v13 & 0x0F),
+            self.v20 & 0x7F,
+            (self.v36 & 0x07) << 4 | (self.v27 & 0x07),
+            self.v43 & 0x7F,
+            (self.j1 & 0x03) << 4 | (self.v50 & 0x0F),
+            self.v57 & 0x1F,
+            self.v59 & 0x1F,
+            self.v61 & 0x3F,
+            self.v62 & 0x3F,
+        ]
+    }
+}
+
+/// Tuning registers of an ST7789 panel, applied by `init`
+///
+/// Voltages are given in millivolts and rounded to the nearest register step; values out of
+/// range are clamped.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PanelConfig {
+    inverted_colors: bool,
+    back_porch: u8,
+    front_porch: u8,
+    vgh: Vgh,
+    vgl: Vgl,
+    vcom_mv: u16,
+    lcm_control: LcmControl,
+    gvdd_mv: u16,
+    vdv_mv: i16,
+    frame_rate_hz: u32,
+    inversion_mode: InversionMode,
+    avdd: Avdd,
+    avcl: Avcl,
+    vds: Vds,
+    positive_gamma: Gamma,
+    negative_gamma: Gamma,
+}
+
+impl PanelConfig {
+    pub const fn new() -> Self {
+        Self {
+            inverted_colors: true,
+            back_porch: 12,
+            front_porch: 12,
+            vgh: Vgh::V13_26,
+            vgl: Vgl::Minus10_43,
+            vcom_mv: 900,
+            lcm_control: LcmControl::DEFAULT,
+            gvdd_mv: 4100,
+            vdv_mv: 0,
+            frame_rate_hz: 60,
+            inversion_mode: InversionMode::Dot,
+            avdd: Avdd::V6_8,
+            avcl: Avcl::Minus4_8,
+            vds: Vds::V2_30,
+            positive_gamma: Gamma::POSITIVE,
+            negative_gamma: Gamma::NEGATIVE,
+        }
+    }
+
+    /// Invert colors with INVON, which IPS modules need to show black as black; on by default
+    pub const fn with_inverted_colors(mut self, inverted: bool) -> Self {
+        self.inverted_colors = inverted;
+        self
+    }
+
+    /// Blank lines before and after each frame (PORCTRL), 1 to 127 each, 12 by default
+    ///
+    /// Longer porches lower the frame rate.
+    pub const fn with_porch(mut self, back: u8, front: u8) -> Self {
+        self.back_porch = clamp(back as i32, 1, 127) as u8;
+        self.front_porch = clamp(front as i32, 1, 127) as u8;
+        self
+    }
+
+    /// Gate driver voltages (GCTRL)
+    pub const fn with_gate_voltages(mut self, vgh: Vgh, vgl: Vgl) -> Self {
+        self.vgh = vgh;
+        self.vgl = vgl;
+        self
+    }
+
+    /// Common electrode voltage (VCOMS), 100 to 1675 mV in 25 mV steps, 900 mV by default
+    ///
+    /// Tune it to minimize flicker.
+    pub const fn with_vcom(mut self, mv: u16) -> Self {
+        self.vcom_mv = mv;
+        self
+    }
+
+    /// Mirroring and color order adjustments (LCMCTRL)
+    pub const fn with_lcm_control(mut self, lcm_control: LcmControl) -> Self {
+        self.lcm_control = lcm_control;
+        self
+    }
+
+    /// Gamma reference voltage GVDD (VRHSET), 3550 to 5500 mV in 50 mV steps, 4100 mV by default
+    pub const fn with_gvdd(mut self, mv: u16) -> Self {
+        self.gvdd_mv = mv;
+        self
+    }
+
+    /// Offset added to the gamma reference voltages (VDVSET), -800 to 775 mV in 25 mV steps
+    pub const fn with_vdv(mut self, mv: i16) -> Self {
+        self.vdv_mv = mv;
+        self
+    }
+
+    /// Refresh rate in normal mode (FRCTRL2), 39 to 116 Hz with the default porches
+    ///
+    /// The nearest rate the panel supports is used, see `frame_rate_hz`.
+    pub const fn with_frame_rate(mut self, hz: u32) -> Self {
+        self.frame_rate_hz = hz;
+        self
+    }
+
+    /// Polarity inversion of the source drivers (FRCTRL2)
+    pub const fn with_inversion_mode(mut self, mode: InversionMode) -> Self {
+        self.inversion_mode = mode;
+        self
+    }
+
+    /// Analog supply voltages (PWCTRL1)
+    pub const fn with_power(mut self, avdd: Avdd, avcl: Avcl, vds: Vds) -> Self {
+        self.avdd = avdd;
+        self.avcl = avcl;
+        self.vds = vds;
+        self
+    }
+
+    /// Gamma curves for positive and negative polarity (PVGAMCTRL and NVGAMCTRL)
+    pub const fn with_gamma(mut self, positive: Gamma, negative: Gamma) -> Self {
+        self.positive_gamma = positive;
+        self.negative_gamma = negative;
+        self
+    }
+
+    /// Refresh rate the panel actually runs at
+    pub const fn frame_rate_hz(&self) -> u32 {
+        FRAME_CLOCK_HZ / (self.lines() * (250 + 16 * self.rtna() as u32))
+    }
+
+    /// Commands that apply the configuration
+    pub const fn commands(&self) -> [InitCommand; CONFIG_COMMANDS] {
+        let inversion = if self.inverted_colors { Command::InvOn } else { Command::InvOff };
+        let nla = match self.inversion_mode {
+            InversionMode::Dot => 0x00,
+            InversionMode::Column => 0x07,
+        };
+        let vcom = clamp((self.vcom_mv as i32 - 100 + 12) / 25, 0, 0x3F);
+        let vrh = clamp((self.gvdd_mv as i32 - 3550 + 25) / 50, 0, 0x27);
+        let vdv = clamp((self.vdv_mv as i32 + 800 + 12) / 25, 0, 0x3F);
+        [
+            InitCommand::new(inversion as u8, &[]),
+            InitCommand::new(Command::PorCtrl as u8, &[self.back_porch, self.front_porch, 0x00, 0x33, 0x33]),
+            InitCommand::new(Command::GCtrl as u8, &[(self.vgh as u8) << 4 | self.vgl as u8]),
+            InitCommand::new(Command::VcomS as u8, &[vcom as u8]),
+            InitCommand::new(Command::LcmCtrl as u8, &[self.lcm_control.bits()]),
+            // Take VRH and VDV from the commands below instead of NVM
+            InitCommand::new(Command::VdvVrhEn as u8, &[0x01, 0xFF]),
+            InitCommand::new(Command::VrhSet as u8, &[vrh as u8]),
+            InitCommand::new(Command::VdvSet as u8, &[vdv as u8]),
+            InitCommand::new(Command::FrCtrl2 as u8, &[nla << 5 | self.rtna()]),
+            InitCommand::new(Command::PwCtrl1 as u8, &[0xA4, (self.avdd as u8) << 6 | (self.avcl as u8) << 4 | self.vds as u8]),
+            InitCommand::new(Command::PvGamCtrl as u8, &self.positive_gamma.to_bytes()),
+            InitCommand::new(Command::NvGamCtrl as u8, &self.negative_gamma.to_bytes()),
+        ]
+    }
+
+    /// Lines per frame: 320 visible ones and the porches
+    const fn lines(&self) -> u32 {
+        320 + self.back_porch as u32 + self.front_porch as u32
+    }
+
+    /// RTNA value of FRCTRL2 that comes closest to the requested frame rate
+    ///
+    /// The panel refreshes at 10 MHz / (lines * (250 + 16 * RTNA)).
+    const fn rtna(&self) -> u8 {
+        let hz = if self.frame_rate_hz == 0 { 1 } else { self.frame_rate_hz };
+        let clocks_per_line = FRAME_CLOCK_HZ / hz.saturating_mul(self.lines());
+        clamp((clocks_per_line as i32 - 250 + 8) / 16, 0, 0x1F) as u8
+    }
+}
+
+impl Default for PanelConfig {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Clock that the ST7789 derives its line timing from
+const FRAME_CLOCK_HZ: u32 = 10_000_000;
+
+const fn clamp(value: i32, min: i32, max: i32) -> i32 {
+    if value < min {
+        min
+    } else if value > max {
+        max
+    } else {
+        value
+    }
+}
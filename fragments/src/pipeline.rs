@@ -0,0 +1,79 @@
+//! Pipelined rendering across two cores
+//!
+//! Instead of both cores working on the same frame, `Pipeline` moves each frame through
+//! stages on different cores: the serving core shades frame N+1 into one buffer while the
+//! lending core finishes frame N in another, e.g. with a post-processing pass or an overlay,
+//! and submits it to the display, which sends frame N-1 from a third buffer. Each frame
+//! reaches the panel one frame later, but the finishing stage no longer adds to the time
+//! per frame.
+
+use core::mem;
+
+use crate::handoff::{Handoff, Strip};
+
+/// Two buffers taking turns at being shaded by the serving core and finished by this one
+///
+/// The third buffer of the rotation is the one the display holds. The finishing stage swaps
+/// it in when it submits its buffer, as `queue_frame` does.
+pub struct Pipeline<'b, T> {
+    /// Buffer that the next frame is shaded into
+    free: &'b mut [u8],
+    /// Buffer shaded with `pending`, waiting to be finished
+    shaded: &'b mut [u8],
+    pending: Option<T>,
+}
+
+impl<'b, T: Send + Copy> Pipeline<'b, T> {
+    /// Rotate `first` and `second`, both holding a whole frame; the first frame is shaded into `first`
+    pub fn new(first: &'b mut [u8], second: &'b mut [u8]) -> Self {
+        Self { free: first, shaded: second, pending: None }
+    }
+
+    /// Lend the free buffer with `data` to the core serving `handoff`, and meanwhile `finish`
+    /// the frame shaded in the previous step
+    ///
+    /// `finish` gets the shaded buffer with the data it was shaded with, and may replace the
+    /// buffer by another one, such as a buffer the display is done with. Returns its result,
+    /// or `None` if no frame was waiting to be finished. The step returns once both stages
+    /// are done, and the newly shaded frame is finished by the next step.
+    ///
+    /// # Panics
+    ///
+    /// If another exchange is in progress on `handoff`.
+    pub fn step<R>(&mut self, handoff: &Handoff<T>, data: T, finish: impl FnOnce(&mut &'b mut [u8], &T) -> R) -> Option<R> {
+        let pending = self.pending.take();
+        let shaded = &mut self.shaded;
+        let result = handoff.run(Strip::new(&mut *self.free, 0), data, || pending.map(|pending| finish(shaded, &pending)));
+        mem::swap(&mut self.free, &mut self.shaded);
+        self.pending = Some(data);
+        result
+    }
+
+    /// Like `step`, but shade on this core too, e.g. once the serving core has failed
+    ///
+    /// The frame shaded in the previous step is finished first, then the free buffer is
+    /// handed to `shade`, so frames keep coming out in the same order.
+    pub fn step_local<R>(
+        &mut self,
+        data: T,
+        shade: impl FnOnce(Strip<'_>, &T),
+        finish: impl FnOnce(&mut &'b mut [u8], &T) -> R,
+    ) -> Option<R> {
+        let result = self.pending.take().map(|pending| finish(&mut self.shaded, &pending));
+        shade(Strip::new(&mut *self.free, 0), &data);
+        mem::swap(&mut self.free, &mut self.shaded);
+        self.pending = Some(data);
+        result
+    }
+
+    /// Data of the frame waiting to be finished
+    pub fn pending(&self) -> Option<&T> {
+        self.pending.as_ref()
+    }
+
+    /// Drop the frame waiting to be finished, e.g. when it has gone stale while the display
+    /// was asleep
+    pub fn discard(&mut self) -> Option<T> {
+        self.pending.take()
+    }
+}
@@ -0,0 +1,52 @@
+//! Power management policy
+//!
+//! Decides when to put the display to sleep and wake it up again, from the time of the last
+//! input. The firmware carries out the transitions, e.g. with `sleep` and `wake` on the
+//! display driver and by lowering the system clock while idle.
+
+use crate::uniforms::InputState;
+
+/// Power state change for the firmware to carry out
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PowerAction {
+    /// No input for the idle timeout: blank the panel and save power
+    Sleep,
+    /// Input while idle: restore full power and the panel
+    Wake,
+}
+
+/// Goes idle after a period without input and wakes on the next button press
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct IdlePolicy {
+    timeout_us: u64,
+    last_input_us: u64,
+    idle: bool,
+}
+
+impl IdlePolicy {
+    /// Go idle `timeout_us` after the last input, counting from `now_us`
+    pub const fn new(timeout_us: u64, now_us: u64) -> Self {
+        Self { timeout_us, last_input_us: now_us, idle: false }
+    }
+
+    pub fn is_idle(&self) -> bool {
+        self.idle
+    }
+
+    /// Feed the input sampled at the counter value `now_us`
+    ///
+    /// Returns the transition to make, if any. Holding a button keeps the display awake.
+    pub fn update(&mut self, now_us: u64, input: InputState) -> Option<PowerAction> {
+        if input.any_pressed() {
+            self.last_input_us = now_us;
+            if self.idle {
+                self.idle = false;
+                return Some(PowerAction::Wake);
+            }
+        } else if !self.idle && now_us.saturating_sub(self.last_input_us) >= self.timeout_us {
+            self.idle = true;
+            return Some(PowerAction::Sleep);
+        }
+        None
+    }
+}
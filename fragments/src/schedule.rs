@@ -0,0 +1,194 @@
+//! Distribution of rendering work across cores
+//!
+//! Frames are split into a range of rows per core, fixed or moved by `AdaptiveSplit` after
+//! every frame, or cut into bands that the cores take from a queue as they go, see
+//! `handoff::Handoff::share`. `CoreStats` counts what each core did to compare them.
+
+use core::ops::Range;
+use core::sync::atomic::{AtomicU32, Ordering};
+
+/// Rows rendered by `core` when `height` rows are split evenly across `cores` cores
+///
+/// Earlier cores get the upper rows. Any remainder goes to the last core.
+pub fn split_rows(height: usize, cores: usize, core: usize) -> Range<usize> {
+    let rows_per_core = height / cores;
+    let start = core * rows_per_core;
+    let end = if core + 1 == cores { height } else { start + rows_per_core };
+    start..end
+}
+
+/// Split of rows between two cores that follows their measured render times
+///
+/// Core 0 gets a share of the rows, starting at half. After each frame, `update` moves the
+/// boundary towards the point where both cores would have finished together, judging by the
+/// time per row each core took. Core 0 also serves interrupts and drives the display, so it
+/// usually ends up with fewer rows. Each core keeps at least `MIN_SHARE` of the rows so that
+/// its speed can still be measured.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct AdaptiveSplit {
+    share: f32,
+}
+
+impl AdaptiveSplit {
+    pub const MIN_SHARE: f32 = 1.0 / 16.0;
+
+    pub const fn new() -> Self {
+        Self { share: 0.5 }
+    }
+
+    /// Share of the rows rendered by core 0, between 0 and 1
+    pub fn share(&self) -> f32 {
+        self.share
+    }
+
+    /// Rows of `rows` rendered by core 0; core 1 renders the rest
+    pub fn core0_rows(&self, rows: usize) -> usize {
+        ((rows as f32 * self.share + 0.5) as usize).min(rows)
+    }
+
+    /// Account for a frame in which core 0 rendered `rows[0]` rows in `busy_us[0]` and core 1
+    /// `rows[1]` rows in `busy_us[1]`
+    ///
+    /// Half of the difference to the balanced split is applied, which smooths out jitter in
+    /// the measurements. Frames in which a core rendered nothing are ignored.
+    pub fn update(&mut self, rows: [usize; 2], busy_us: [u64; 2]) {
+        if rows.contains(&0) || busy_us.contains(&0) {
+            return;
+        }
+        let [cost0, cost1] = [0, 1].map(|core| busy_us[core] as f32 / rows[core] as f32);
+        let balanced = cost1 / (cost0 + cost1);
+        self.share = (self.share + (balanced - self.share) * 0.5).clamp(Self::MIN_SHARE, 1.0 - Self::MIN_SHARE);
+    }
+}
+
+impl Default for AdaptiveSplit {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Number of cores sharing the rendering work
+pub const CORES: usize = 2;
+
+/// Work done by one core over a period of time
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct CoreLoad {
+    /// Bands or strips rendered
+    pub bands: u32,
+    pub rows: u32,
+    /// Time spent rendering them
+    pub busy_us: u32,
+}
+
+impl CoreLoad {
+    /// Percentage of `elapsed_us` spent rendering
+    pub fn utilization_percent(&self, elapsed_us: u64) -> u32 {
+        (self.busy_us as u64 * 100).checked_div(elapsed_us).map_or(0, |percent| percent.min(100) as u32)
+    }
+}
+
+struct LoadCounters {
+    bands: AtomicU32,
+    rows: AtomicU32,
+    busy_us: AtomicU32,
+    /// Busy time of the latest band, kept across `take`
+    last_busy_us: AtomicU32,
+}
+
+/// Per-core work counters, recorded by each core as it finishes a band
+///
+/// The counters are atomic, so each core records into its own while the other reads or
+/// `take`s them. They wrap after about 71 minutes of busy time, so read and reset them
+/// regularly with `take`.
+pub struct CoreStats {
+    cores: [LoadCounters; CORES],
+}
+
+impl CoreStats {
+    pub const fn new() -> Self {
+        Self {
+            cores: [const {
+                LoadCounters {
+                    bands: AtomicU32::new(0),
+                    rows: AtomicU32::new(0),
+                    busy_us: AtomicU32::new(0),
+                    last_busy_us: AtomicU32::new(0),
+                }
+            }; CORES],
+        }
+    }
+
+    /// Count `rows` rendered by `core` in `busy_us`
+    pub fn record(&self, core: usize, rows: usize, busy_us: u64) {
+        let counters = &self.cores[core];
+        counters.bands.fetch_add(1, Ordering::Relaxed);
+        counters.rows.fetch_add(rows as u32, Ordering::Relaxed);
+        let busy_us = busy_us.min(u32::MAX as u64) as u32;
+        counters.busy_us.fetch_add(busy_us, Ordering::Relaxed);
+        counters.last_busy_us.store(busy_us, Ordering::Relaxed);
+    }
+
+    /// Time `core` took for the band it recorded last, e.g. its part of the previous frame
+    /// with a fixed or adaptive split
+    pub fn last_busy_us(&self, core: usize) -> u32 {
+        self.cores[core].last_busy_us.load(Ordering::Relaxed)
+    }
+
+    /// Work done by each core since the previous call, resetting the counters
+    pub fn take(&self) -> [CoreLoad; CORES] {
+        core::array::from_fn(|core| {
+            let counters = &self.cores[core];
+            CoreLoad {
+                bands: counters.bands.swap(0, Ordering::Relaxed),
+                rows: counters.rows.swap(0, Ordering::Relaxed),
+                busy_us: counters.busy_us.swap(0, Ordering::Relaxed),
+            }
+        })
+    }
+}
+
+impl Default for CoreStats {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Per-core load over consecutive windows of time, taken from `CoreStats`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct LoadMonitor {
+    window_us: u64,
+    start_us: u64,
+    loads: [CoreLoad; CORES],
+    elapsed_us: u64,
+}
+
+impl LoadMonitor {
+    /// Sum up windows of `window_us`, the first one starting at the counter value `now_us`
+    pub const fn new(window_us: u64, now_us: u64) -> Self {
+        Self { window_us, start_us: now_us, loads: [CoreLoad { bands: 0, rows: 0, busy_us: 0 }; CORES], elapsed_us: 0 }
+    }
+
+    /// Close the current window at `now_us` if it is complete
+    ///
+    /// Returns whether a new window has been summed up.
+    pub fn update(&mut self, now_us: u64, stats: &CoreStats) -> bool {
+        let elapsed_us = now_us.saturating_sub(self.start_us);
+        if elapsed_us < self.window_us {
+            return false;
+        }
+        self.loads = stats.take();
+        self.elapsed_us = elapsed_us;
+        self.start_us = now_us;
+        true
+    }
+
+    /// Work of each core in the last complete window
+    pub fn loads(&self) -> [CoreLoad; CORES] {
+        self.loads
+    }
+
+    /// Share of the last complete window that `core` spent rendering, in percent
+    pub fn utilization_percent(&self, core: usize) -> u32 {
+        self.loads[core].utilization_percent(self.elapsed_us)
+    }
+}
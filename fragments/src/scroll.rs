@@ -0,0 +1,75 @@
+//! Hardware vertical scrolling
+//!
+//! The panel can show its frame memory rotated by a number of rows within a scroll area,
+//! between fixed areas at the top and bottom. Moving the content then takes one command
+//! instead of a new frame, and only the rows that scroll into view need to be sent.
+
+use crate::framebuffer::Rect;
+
+/// Division of a frame into fixed rows at the top, rows that scroll, and fixed rows at the
+/// bottom, adding up to the frame height
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ScrollArea {
+    pub top_fixed: u16,
+    pub scroll_height: u16,
+    pub bottom_fixed: u16,
+}
+
+impl ScrollArea {
+    pub const fn new(top_fixed: u16, scroll_height: u16, bottom_fixed: u16) -> Self {
+        Self { top_fixed, scroll_height, bottom_fixed }
+    }
+
+    /// Frame row that is shown at `row` of the scroll area when scrolled by `offset` rows
+    ///
+    /// Returns `None` for an area without scrolling rows or beyond the last frame row.
+    pub const fn source_row(&self, row: u16, offset: u16) -> Option<u16> {
+        if self.scroll_height == 0 {
+            return None;
+        }
+        let row = (row as u32 + offset as u32) % self.scroll_height as u32;
+        self.top_fixed.checked_add(row as u16)
+    }
+}
+
+/// Log view on top of a scroll area: every new line is written over the oldest one and
+/// scrolled into view at the bottom, so one line is sent per step instead of a whole frame
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ScrollLog {
+    area: ScrollArea,
+    width: u16,
+    line_height: u16,
+    offset: u16,
+}
+
+impl ScrollLog {
+    /// Log of `line_height` rows per line in the scroll area of a `width` pixels wide frame
+    ///
+    /// Returns `None` unless the scroll area holds a whole number of lines.
+    pub const fn new(area: ScrollArea, width: u16, line_height: u16) -> Option<Self> {
+        if line_height == 0 || area.scroll_height == 0 || !area.scroll_height.is_multiple_of(line_height) {
+            return None;
+        }
+        Some(Self { area, width, line_height, offset: 0 })
+    }
+
+    pub const fn area(&self) -> ScrollArea {
+        self.area
+    }
+
+    /// Scroll offset that shows the lines in order, the latest at the bottom
+    pub const fn offset(&self) -> u16 {
+        self.offset
+    }
+
+    /// Frame rows that the next line has to be written to, currently holding the oldest line
+    pub const fn next_line(&self) -> Rect {
+        Rect::new(0, self.area.top_fixed + self.offset, self.width, self.line_height)
+    }
+
+    /// Account for a line written to `next_line` and return the offset to scroll to
+    pub fn advance(&mut self) -> u16 {
+        self.offset = (self.offset + self.line_height) % self.area.scroll_height;
+        self.offset
+    }
+}
@@ -0,0 +1,60 @@
+//! Fragment shader interface
+//!
+//! A fragment shader computes the color of a single pixel from its normalized
+//! screen coordinates and the per-frame uniforms. `render_rows` drives a shader
+//! over a range of rows of a frame buffer, `render_strip` over rows kept in a
+//! buffer that holds only part of the frame.
+
+use core::ops::Range;
+
+use crate::color::{Color, PixelFormat};
+use crate::framebuffer::row_bytes;
+use crate::uniforms::Uniforms;
+
+/// A per-pixel program that determines the color of every pixel of a frame
+pub trait FragmentShader {
+    /// Compute the color of the pixel at normalized coordinates `(u, v)`
+    ///
+    /// Both coordinates lie in [0, 1], `u` running from left to right and `v` from top to bottom.
+    fn shade(&self, u: f32, v: f32, uniforms: &Uniforms) -> Color;
+}
+
+/// Render the given rows of a frame buffer with a fragment shader
+///
+/// `buffer` holds the whole frame of `width` x `height` pixels in the given pixel format.
+/// Only the pixels in `rows` are written.
+pub fn render_rows<S: FragmentShader + ?Sized>(
+    shader: &S,
+    uniforms: &Uniforms,
+    format: PixelFormat,
+    buffer: &mut [u8],
+    width: usize,
+    height: usize,
+    rows: Range<usize>,
+) {
+    render_strip(shader, uniforms, format, buffer, width, height, 0, rows);
+}
+
+/// Render the given rows of a frame into a strip buffer that starts at row `first_row`
+///
+/// `strip` holds consecutive rows of a `width` x `height` frame, beginning with `first_row`,
+/// so a strip of a few rows is enough to stream a frame to the display.
+#[allow(clippy::too_many_arguments)]
+pub fn render_strip<S: FragmentShader + ?Sized>(
+    shader: &S,
+    uniforms: &Uniforms,
+    format: PixelFormat,
+    strip: &mut [u8],
+    width: usize,
+    height: usize,
+    first_row: usize,
+    rows: Range<usize>,
+) {
+    let u_scale = 1.0f32 / ((width - 1) as f32);
+    let v_scale = 1.0f32 / ((height - 1) as f32);
+    for y in rows {
+        let v = y as f32 * v_scale;
+        let row = &mut strip[row_bytes(format, width, y - first_row..y - first_row + 1)];
+        format.encode(row, (0..width).map(|x| shader.shade(x as f32 * u_scale, v, uniforms)));
+    }
+}
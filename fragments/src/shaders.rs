@@ -0,0 +1,26 @@
+//! Collection of fragment shaders
+
+use crate::color::Color;
+use crate::shader::FragmentShader;
+use crate::uniforms::Uniforms;
+
+/// Horizontal red and vertical green gradient with a blue channel that ramps up every eight seconds
+pub struct Gradient;
+
+impl FragmentShader for Gradient {
+    fn shade(&self, u: f32, v: f32, uniforms: &Uniforms) -> Color {
+        let t = uniforms.time / 8.0f32;
+        let b = t - (t as u32) as f32;
+        Color::new(u, v, b)
+    }
+}
+
+/// All shipped shaders together with the names under which tools refer to them
+pub static ALL: &[(&str, &(dyn FragmentShader + Sync))] = &[
+    ("gradient", &Gradient),
+];
+
+/// Look up a shipped shader by name
+pub fn by_name(name: &str) -> Option<&'static (dyn FragmentShader + Sync)> {
+    ALL.iter().find(|(n, _)| *n == name).map(|(_, shader)| *shader)
+}
@@ -0,0 +1,225 @@
+//! Supervision of the core that serves the handoff
+//!
+//! The serving core `beat`s a `Heartbeat` as it gets through its work, and a `StallDetector`
+//! tells the lending core when it has waited too long without a beat. Panics on either core
+//! go into a `FaultLog`, which survives a reset when placed in RAM that is not initialized at
+//! boot. A `RestartPolicy` decides whether a failed core is restarted or left off, with its
+//! work done by the remaining core.
+
+use core::cell::UnsafeCell;
+use core::fmt::{self, Write};
+use core::sync::atomic::{AtomicU32, AtomicU8, Ordering};
+
+/// Progress counter bumped by a core as it works
+pub struct Heartbeat {
+    beats: AtomicU32,
+}
+
+impl Heartbeat {
+    pub const fn new() -> Self {
+        Self { beats: AtomicU32::new(0) }
+    }
+
+    pub fn beat(&self) {
+        self.beats.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Beats so far, wrapping around
+    pub fn count(&self) -> u32 {
+        self.beats.load(Ordering::Relaxed)
+    }
+}
+
+impl Default for Heartbeat {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Notices a `Heartbeat` that has stopped while a core waits for it
+///
+/// `arm` it when starting to wait, e.g. once per frame, so that time spent without work for
+/// the other core does not count as a stall.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct StallDetector {
+    timeout_us: u64,
+    beats: u32,
+    since_us: u64,
+}
+
+impl StallDetector {
+    /// Detect `timeout_us` without a beat
+    pub const fn new(timeout_us: u64) -> Self {
+        Self { timeout_us, beats: 0, since_us: 0 }
+    }
+
+    /// Start waiting at the counter value `now_us`
+    pub fn arm(&mut self, now_us: u64, heartbeat: &Heartbeat) {
+        self.beats = heartbeat.count();
+        self.since_us = now_us;
+    }
+
+    /// Whether there was no beat for the timeout until `now_us`
+    pub fn stalled(&mut self, now_us: u64, heartbeat: &Heartbeat) -> bool {
+        let beats = heartbeat.count();
+        if beats != self.beats {
+            self.arm(now_us, heartbeat);
+            return false;
+        }
+        now_us.saturating_sub(self.since_us) >= self.timeout_us
+    }
+}
+
+/// Bytes of a panic message kept in a `Fault`; longer messages are cut off
+pub const FAULT_MESSAGE_LEN: usize = 120;
+
+/// Panic of a core
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Fault {
+    pub core: u8,
+    message: [u8; FAULT_MESSAGE_LEN],
+    len: usize,
+}
+
+impl Fault {
+    /// Panic message with its location, cut off after `FAULT_MESSAGE_LEN` bytes
+    pub fn message(&self) -> &str {
+        core::str::from_utf8(&self.message[..self.len]).unwrap_or_default()
+    }
+}
+
+/// Writes into a message buffer, cutting off what does not fit at a character boundary
+struct MessageWriter<'a> {
+    message: &'a mut [u8; FAULT_MESSAGE_LEN],
+    len: usize,
+}
+
+impl Write for MessageWriter<'_> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        let mut len = s.len().min(FAULT_MESSAGE_LEN - self.len);
+        while !s.is_char_boundary(len) {
+            len -= 1;
+        }
+        self.message[self.len..self.len + len].copy_from_slice(&s.as_bytes()[..len]);
+        self.len += len;
+        Ok(())
+    }
+}
+
+// States of a fault log; anything else is empty, e.g. garbage in RAM after power-up
+const WRITING: u32 = 0x5752_4954;
+const RECORDED: u32 = 0x4641_554C;
+
+/// Slot for the first fault of either core, written by the panic handler
+///
+/// Takes no locks, so a core that panicked while holding one can still record its fault.
+/// Later faults are dropped until the recorded one is taken. Place it in a section that is
+/// not initialized at boot, such as `.uninit` with cortex-m-rt, to read the fault of a core
+/// that reset the chip after it.
+pub struct FaultLog {
+    state: AtomicU32,
+    core: AtomicU8,
+    message: UnsafeCell<([u8; FAULT_MESSAGE_LEN], usize)>,
+}
+
+// The message is only written by the core that moved the state to WRITING, and only read
+// while the state is RECORDED
+unsafe impl Sync for FaultLog {}
+
+impl FaultLog {
+    pub const fn new() -> Self {
+        Self { state: AtomicU32::new(0), core: AtomicU8::new(0), message: UnsafeCell::new(([0; FAULT_MESSAGE_LEN], 0)) }
+    }
+
+    /// Record a fault of `core` with `message`
+    ///
+    /// Returns whether it was recorded, i.e. no other fault was recorded or being recorded.
+    pub fn record(&self, core: u8, message: fmt::Arguments<'_>) -> bool {
+        let state = self.state.load(Ordering::Relaxed);
+        if state == WRITING
+            || state == RECORDED
+            || self.state.compare_exchange(state, WRITING, Ordering::Acquire, Ordering::Relaxed).is_err()
+        {
+            return false;
+        }
+        self.core.store(core, Ordering::Relaxed);
+        // SAFETY: WRITING keeps everybody else away from the message
+        let (bytes, len) = unsafe { &mut *self.message.get() };
+        let mut writer = MessageWriter { message: bytes, len: 0 };
+        let _ = writer.write_fmt(message);
+        *len = writer.len;
+        self.state.store(RECORDED, Ordering::Release);
+        true
+    }
+
+    /// Give up on a fault whose recording never finished, e.g. because its core was stopped
+    /// in the middle of it, making room for the next one
+    ///
+    /// Returns whether there was one. Only call it while no core is recording, such as right
+    /// after stopping the only other core.
+    pub fn abandon_unfinished(&self) -> bool {
+        self.state.compare_exchange(WRITING, 0, Ordering::Relaxed, Ordering::Relaxed).is_ok()
+    }
+
+    /// The recorded fault, if any
+    pub fn get(&self) -> Option<Fault> {
+        if self.state.load(Ordering::Acquire) != RECORDED {
+            return None;
+        }
+        // SAFETY: a recorded message is not written again until it is taken
+        let (message, len) = unsafe { *self.message.get() };
+        Some(Fault { core: self.core.load(Ordering::Relaxed), message, len: len.min(FAULT_MESSAGE_LEN) })
+    }
+
+    /// Take the recorded fault, making room for the next one
+    pub fn take(&self) -> Option<Fault> {
+        let fault = self.get();
+        if fault.is_some() {
+            self.state.store(0, Ordering::Release);
+        }
+        fault
+    }
+}
+
+impl Default for FaultLog {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// What to do about a core that failed
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Recovery {
+    /// Reset the core and start its task again
+    Restart,
+    /// Leave the core off and do its work on the remaining core
+    SingleCore,
+}
+
+/// Restarts a failed core a limited number of times, then carries on without it
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RestartPolicy {
+    max_restarts: u32,
+    restarts: u32,
+}
+
+impl RestartPolicy {
+    pub const fn new(max_restarts: u32) -> Self {
+        Self { max_restarts, restarts: 0 }
+    }
+
+    /// Decide how to recover from another failure
+    pub fn on_failure(&mut self) -> Recovery {
+        if self.restarts < self.max_restarts {
+            self.restarts += 1;
+            Recovery::Restart
+        } else {
+            Recovery::SingleCore
+        }
+    }
+
+    /// Restarts so far
+    pub fn restarts(&self) -> u32 {
+        self.restarts
+    }
+}
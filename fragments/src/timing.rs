@@ -0,0 +1,81 @@
+//! Frame time measurement
+//!
+//! Statistics over durations taken from a free-running microsecond counter, such as the
+//! RP2350 timer, to see how long frames or parts of them take on the hardware.
+
+/// Running statistics of frame or section durations in microseconds
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FrameTimer {
+    last_us: Option<u64>,
+    count: u32,
+    total_us: u64,
+    min_us: u32,
+    max_us: u32,
+}
+
+impl Default for FrameTimer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl FrameTimer {
+    pub const fn new() -> Self {
+        Self {
+            last_us: None,
+            count: 0,
+            total_us: 0,
+            min_us: u32::MAX,
+            max_us: 0,
+        }
+    }
+
+    /// Mark the start of a frame at the counter value `now_us`
+    ///
+    /// The time since the previous mark is recorded as one frame.
+    pub fn tick(&mut self, now_us: u64) {
+        if let Some(last_us) = self.last_us {
+            self.record(now_us.saturating_sub(last_us));
+        }
+        self.last_us = Some(now_us);
+    }
+
+    /// Record a duration measured elsewhere, e.g. the time spent waiting in `swap_buffers`
+    pub fn record(&mut self, duration_us: u64) {
+        let duration_us = duration_us.min(u32::MAX as u64) as u32;
+        self.count = self.count.saturating_add(1);
+        self.total_us += duration_us as u64;
+        self.min_us = self.min_us.min(duration_us);
+        self.max_us = self.max_us.max(duration_us);
+    }
+
+    /// Number of durations recorded
+    pub fn count(&self) -> u32 {
+        self.count
+    }
+
+    pub fn average_us(&self) -> u32 {
+        if self.count == 0 { 0 } else { (self.total_us / self.count as u64) as u32 }
+    }
+
+    pub fn min_us(&self) -> u32 {
+        if self.count == 0 { 0 } else { self.min_us }
+    }
+
+    pub fn max_us(&self) -> u32 {
+        self.max_us
+    }
+
+    /// Frames per second corresponding to the average duration
+    pub fn fps(&self) -> f32 {
+        match self.average_us() {
+            0 => 0.0,
+            average_us => 1e6 / average_us as f32,
+        }
+    }
+
+    /// Forget all recorded durations, e.g. to start a new measurement window
+    pub fn reset(&mut self) {
+        *self = Self::new();
+    }
+}
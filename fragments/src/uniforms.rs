@@ -0,0 +1,101 @@
+//! Per-frame shader inputs
+
+/// User button of the Waveshare Pico LCD 2
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Button {
+    Key0 = 0,
+    Key1 = 1,
+    Key2 = 2,
+    Key3 = 3,
+}
+
+/// Snapshot of the user buttons taken at the start of a frame
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct InputState {
+    buttons: u8,
+}
+
+impl InputState {
+    /// Create an input state from the pressed flags of `Key0` to `Key3`
+    pub fn from_pressed(pressed: [bool; 4]) -> Self {
+        let buttons = pressed
+            .iter()
+            .enumerate()
+            .fold(0u8, |bits, (i, &p)| bits | ((p as u8) << i));
+        Self { buttons }
+    }
+
+    /// Whether the given button was held down
+    pub fn is_pressed(&self, button: Button) -> bool {
+        self.buttons & (1 << button as u8) != 0
+    }
+
+    /// Whether any button was held down
+    pub fn any_pressed(&self) -> bool {
+        self.buttons != 0
+    }
+}
+
+/// Values that are constant across all pixels of a frame
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Uniforms {
+    /// Seconds elapsed since rendering started
+    pub time: f32,
+    /// Seconds elapsed since the previous frame
+    pub delta_time: f32,
+    /// Index of the current frame
+    pub frame: u32,
+    /// Width and height of the frame in pixels
+    pub resolution: [f32; 2],
+    /// Width divided by height
+    pub aspect_ratio: f32,
+    /// Button state at the start of the frame
+    pub input: InputState,
+}
+
+impl Uniforms {
+    pub fn new(time: f32, delta_time: f32, frame: u32, width: u16, height: u16, input: InputState) -> Self {
+        Self {
+            time,
+            delta_time,
+            frame,
+            resolution: [width as f32, height as f32],
+            aspect_ratio: width as f32 / height as f32,
+            input,
+        }
+    }
+}
+
+/// Derives the timing uniforms of consecutive frames from a free-running microsecond counter
+#[derive(Clone, Copy, Debug)]
+pub struct FrameClock {
+    start_us: u64,
+    last_us: u64,
+    frame: u32,
+}
+
+impl FrameClock {
+    /// Start the clock at the counter value `now_us`
+    pub fn new(now_us: u64) -> Self {
+        Self {
+            start_us: now_us,
+            last_us: now_us,
+            frame: 0,
+        }
+    }
+
+    /// Build the uniforms of the next frame, which starts at the counter value `now_us`
+    pub fn next_frame(&mut self, now_us: u64, width: u16, height: u16, input: InputState) -> Uniforms {
+        let uniforms = Uniforms::new(
+            (now_us - self.start_us) as f32 * 1e-6f32,
+            (now_us - self.last_us) as f32 * 1e-6f32,
+            self.frame,
+            width,
+            height,
+            input,
+        );
+        self.last_us = now_us;
+        self.frame = self.frame.wrapping_add(1);
+        uniforms
+    }
+}
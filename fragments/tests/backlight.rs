@@ -0,0 +1,60 @@
+//! Tests of the gamma-corrected backlight and its fades
+
+mod support;
+
+use embedded_hal::pwm::ErrorKind as PwmErrorKind;
+use fragments::backlight::gamma_duty;
+use fragments::display::DisplayError;
+use support::{mock_display, MockPwm};
+
+#[test]
+fn brightness_follows_a_gamma_curve() {
+    assert_eq!(gamma_duty(0, 1000), 0);
+    assert_eq!(gamma_duty(255, 1000), 1000);
+    assert_eq!(gamma_duty(255, u16::MAX), u16::MAX);
+    // Half the perceived brightness takes under a quarter of the power
+    assert!((200..240).contains(&gamma_duty(128, 1000)));
+    for level in 0..255 {
+        assert!(gamma_duty(level, 1000) <= gamma_duty(level + 1, 1000));
+    }
+}
+
+#[test]
+fn fades_advance_with_the_clock() {
+    let pwm = MockPwm::default();
+    let (display, _buffer, _delay, _log) = mock_display();
+    let mut display = display.with_backlight(pwm.clone());
+    assert_eq!(display.brightness(), 0);
+
+    display.fade_brightness(200, 1_000_000, 5_000_000);
+    display.update_backlight(5_000_000).unwrap();
+    assert_eq!(display.brightness(), 0);
+    display.update_backlight(5_500_000).unwrap();
+    assert_eq!(display.brightness(), 100);
+    display.update_backlight(7_000_000).unwrap();
+    assert_eq!(display.brightness(), 200);
+    assert_eq!(*pwm.duties.borrow(), [0, gamma_duty(100, 1000), gamma_duty(200, 1000)]);
+
+    // Without a fade, updates leave the PWM alone
+    display.update_backlight(8_000_000).unwrap();
+    assert_eq!(pwm.duties.borrow().len(), 3);
+
+    // Setting the brightness cancels a fade
+    display.fade_brightness(0, 1_000_000, 8_000_000);
+    display.set_brightness(255).unwrap();
+    display.update_backlight(8_500_000).unwrap();
+    assert_eq!(display.brightness(), 255);
+    assert_eq!(pwm.duties.borrow().last(), Some(&1000));
+}
+
+#[test]
+fn pwm_errors_are_reported() {
+    let pwm = MockPwm::default();
+    let (display, _buffer, _delay, _log) = mock_display();
+    let mut display = display.with_backlight(pwm.clone());
+    *pwm.fail.borrow_mut() = true;
+    assert_eq!(display.set_brightness(100), Err(DisplayError::Backlight(PwmErrorKind::Other)));
+    assert_eq!(display.brightness(), 0);
+    display.set_brightness(100).unwrap();
+    assert_eq!(display.brightness(), 100);
+}
@@ -0,0 +1,505 @@
+//! Command-stream tests of the display driver against an emulated ST7789
+
+mod support;
+
+use std::collections::HashSet;
+
+use embedded_hal::digital::ErrorKind as PinErrorKind;
+use embedded_hal::spi::ErrorKind as SpiErrorKind;
+use fragments::color::PixelFormat;
+use fragments::display::{DisplayError, Orientation, Rotation};
+use fragments::framebuffer::{buffer_size, pack_region, DirtyRegion, Rect, HEIGHT, WIDTH};
+use fragments::shader::{render_rows, render_strip};
+use fragments::shaders::Gradient;
+use fragments::uniforms::{InputState, Uniforms};
+use support::st7789::St7789;
+use support::{leak_buffer, mock_display, mock_display_in, mock_display_with, Event, MockPwm, Pin};
+
+fn render_gradient(format: PixelFormat, buffer: &mut [u8]) {
+    let uniforms = Uniforms::new(3.0, 0.03, 90, WIDTH, HEIGHT, InputState::default());
+    render_rows(&Gradient, &uniforms, format, buffer, WIDTH as usize, HEIGHT as usize, 0..HEIGHT as usize);
+}
+
+#[test]
+fn init_resets_and_configures_panel() {
+    let (mut display, _buffer, mut delay, log) = mock_display();
+    display.init(&mut delay).unwrap();
+    let events = log.take();
+
+    // The hardware reset pulse comes before any bus traffic
+    let reset = events.iter().position(|e| *e == Event::Pin(Pin::Rst, false)).unwrap();
+    let first_write = events.iter().position(|e| matches!(e, Event::Write(_))).unwrap();
+    assert!(reset < first_write);
+
+    let mut panel = St7789::default();
+    panel.process(events);
+    assert_eq!(panel.commands.first(), Some(&0x01));
+    assert!(!panel.sleeping);
+    assert!(panel.display_on);
+    assert!(panel.inverted);
+    assert_eq!(panel.colmod, 0x06);
+    assert_eq!(panel.madctl, 0x00);
+    assert_eq!(panel.columns, (0, WIDTH - 1));
+    assert_eq!(panel.rows, (0, HEIGHT - 1));
+    assert_eq!(panel.parameters[&0xE0].len(), 14);
+    assert_eq!(panel.parameters[&0xE1].len(), 14);
+    assert!(panel.image().iter().all(|&c| c == 0));
+    assert_eq!(panel.unflushed_pin_changes, 0);
+}
+
+#[test]
+fn swap_buffers_transmits_rendered_frame() {
+    let (mut display, mut buffer, mut delay, log) = mock_display();
+    display.init(&mut delay).unwrap();
+    let mut panel = St7789::default();
+    panel.process(log.take());
+
+    render_gradient(PixelFormat::Rgb666, buffer);
+    let frame = buffer.to_vec();
+    let frame_ptr = buffer.as_ptr();
+
+    display.swap_buffers(&mut delay, &mut buffer).unwrap();
+    assert_ne!(buffer.as_ptr(), frame_ptr);
+
+    panel.process(log.take());
+    assert_eq!(panel.commands_since(0x2C), &[] as &[u8]);
+    // Clearing in init and the swapped-in frame
+    assert_eq!(panel.pixels_written, 2 * WIDTH as usize * HEIGHT as usize);
+    assert!(panel.image() == frame);
+}
+
+#[test]
+fn swap_before_init_is_rejected() {
+    let (mut display, mut buffer, mut delay, log) = mock_display();
+    let buffer_ptr = buffer.as_ptr();
+    assert_eq!(display.swap_buffers(&mut delay, &mut buffer), Err(DisplayError::Uninitialized));
+    assert_eq!(buffer.as_ptr(), buffer_ptr);
+    assert!(log.take().is_empty());
+}
+
+#[test]
+fn bus_and_pin_errors_are_reported() {
+    let (mut display, _buffer, mut delay, log) = mock_display();
+    log.fail_next_pin_change(Pin::Rst);
+    assert_eq!(display.init(&mut delay), Err(DisplayError::Pin(PinErrorKind::Other)));
+    log.fail_next_spi_write();
+    assert_eq!(display.init(&mut delay), Err(DisplayError::Bus(SpiErrorKind::Other)));
+}
+
+#[test]
+fn reinit_recovers_after_failed_swap() {
+    let (mut display, mut buffer, mut delay, log) = mock_display();
+    display.init(&mut delay).unwrap();
+    display.swap_buffers(&mut delay, &mut buffer).unwrap();
+
+    render_gradient(PixelFormat::Rgb666, buffer);
+    let frame = buffer.to_vec();
+    log.fail_next_spi_write();
+    assert_eq!(display.swap_buffers(&mut delay, &mut buffer), Err(DisplayError::Bus(SpiErrorKind::Other)));
+    // The frame that could not be sent stays with the caller
+    assert!(buffer[..] == frame[..]);
+
+    display.init(&mut delay).unwrap();
+    display.swap_buffers(&mut delay, &mut buffer).unwrap();
+    let mut panel = St7789::default();
+    panel.process(log.take());
+    assert!(panel.image() == frame);
+}
+
+#[test]
+fn init_is_rejected_while_a_transfer_is_in_flight() {
+    let (mut display, mut buffer, mut delay, log) = mock_display();
+    display.init(&mut delay).unwrap();
+    display.swap_buffers(&mut delay, &mut buffer).unwrap();
+    log.set_dma_busy(true);
+    assert_eq!(display.init(&mut delay), Err(DisplayError::TransferInFlight));
+    log.set_dma_busy(false);
+    assert_eq!(display.init(&mut delay), Ok(()));
+}
+
+#[test]
+fn frames_are_sent_in_the_selected_pixel_format() {
+    for format in [PixelFormat::Rgb444, PixelFormat::Rgb565, PixelFormat::Rgb666] {
+        let (mut display, mut buffer, mut delay, log) = mock_display_in(format);
+        display.init(&mut delay).unwrap();
+        render_gradient(format, buffer);
+        let mut frame = vec![0u8; 3 * WIDTH as usize * HEIGHT as usize];
+        format.decode(buffer, &mut frame);
+        display.swap_buffers(&mut delay, &mut buffer).unwrap();
+
+        let mut panel = St7789::default();
+        panel.process(log.take());
+        assert_eq!(panel.colmod, format.colmod());
+        assert!(panel.image() == frame, "{:?} frame differs", format);
+    }
+}
+
+#[test]
+fn buffers_of_the_wrong_size_are_rejected() {
+    let (mut display, _buffer, mut delay, _log) = mock_display_in(PixelFormat::Rgb565);
+    display.init(&mut delay).unwrap();
+    let mut buffer = leak_buffer(buffer_size(PixelFormat::Rgb666));
+    assert_eq!(display.swap_buffers(&mut delay, &mut buffer), Err(DisplayError::BufferSize));
+}
+
+#[test]
+fn orientation_maps_frames_onto_the_panel() {
+    // Logical pixel (1, 0) and where it has to end up on the portrait panel
+    let cases = [
+        (Orientation::new(Rotation::Deg0, false), 0x00, (1, 0)),
+        (Orientation::new(Rotation::Deg90, false), 0x60, (WIDTH - 1, 1)),
+        (Orientation::new(Rotation::Deg180, false), 0xC0, (WIDTH - 2, HEIGHT - 1)),
+        (Orientation::new(Rotation::Deg270, false), 0xA0, (0, HEIGHT - 2)),
+        (Orientation::new(Rotation::Deg0, true), 0x40, (WIDTH - 2, 0)),
+        (Orientation::new(Rotation::Deg90, true), 0xE0, (WIDTH - 1, HEIGHT - 2)),
+    ];
+    for (orientation, madctl, (x, y)) in cases {
+        let (mut display, mut buffer, mut delay, log) = mock_display();
+        display.init(&mut delay).unwrap();
+        display.set_orientation(&mut delay, orientation).unwrap();
+        let (width, height) = (display.width(), display.height());
+        assert_eq!((width, height), orientation.size(WIDTH, HEIGHT));
+
+        buffer.fill(0);
+        buffer[3..6].copy_from_slice(&[0xFC; 3]);
+        display.swap_buffers(&mut delay, &mut buffer).unwrap();
+
+        let mut panel = St7789::default();
+        panel.process(log.take());
+        assert_eq!(panel.madctl, madctl, "{:?}", orientation);
+        assert_eq!(panel.columns, (0, width - 1));
+        assert_eq!(panel.rows, (0, height - 1));
+        let image = panel.image();
+        let lit: Vec<_> = (0..image.len() / 3).filter(|&i| image[3 * i] != 0).collect();
+        assert_eq!(lit, [y as usize * WIDTH as usize + x as usize], "{:?}", orientation);
+    }
+}
+
+#[test]
+fn orientation_is_applied_by_init() {
+    let (display, _buffer, mut delay, log) = mock_display();
+    let mut display = display.with_orientation(Orientation::new(Rotation::Deg270, false));
+    assert_eq!((display.width(), display.height()), (HEIGHT, WIDTH));
+    display.init(&mut delay).unwrap();
+
+    let mut panel = St7789::default();
+    panel.process(log.take());
+    assert_eq!(panel.madctl, 0xA0);
+    assert_eq!(panel.columns, (0, HEIGHT - 1));
+    assert_eq!(panel.rows, (0, WIDTH - 1));
+}
+
+#[test]
+fn regions_update_only_the_changed_pixels() {
+    for format in [PixelFormat::Rgb444, PixelFormat::Rgb565, PixelFormat::Rgb666] {
+        let (mut display, mut buffer, mut delay, log) = mock_display_in(format);
+        display.init(&mut delay).unwrap();
+        render_gradient(format, buffer);
+        let mut frame = buffer.to_vec();
+        display.swap_buffers(&mut delay, &mut buffer).unwrap();
+        let mut panel = St7789::default();
+        panel.process(log.take());
+
+        // Draw two overlays into the background and send their bounding box
+        let mut dirty = DirtyRegion::new();
+        for rect in [Rect::new(11, 20, 30, 5), Rect::new(60, 40, 9, 12)] {
+            let stride = format.bytes_for(WIDTH as usize);
+            for row in rect.y as usize..rect.bottom() as usize {
+                let start = row * stride + format.bytes_for(rect.x as usize);
+                frame[start..start + format.bytes_for(rect.width as usize)].fill(0xF0);
+            }
+            dirty.mark(rect);
+        }
+        let region = dirty.take(format).unwrap();
+        assert!(dirty.is_clean());
+        assert_eq!(region, Rect::new(11, 20, 58, 32).aligned(format));
+
+        let len = pack_region(format, &frame, WIDTH as usize, region, buffer);
+        assert_eq!(len, format.bytes_for(region.area()));
+        display.swap_region(&mut delay, region, &mut buffer).unwrap();
+        panel.process(log.take());
+        assert_eq!(panel.columns, (region.x, region.right() - 1));
+        assert_eq!(panel.rows, (region.y, region.bottom() - 1));
+
+        let mut expected = vec![0u8; 3 * WIDTH as usize * HEIGHT as usize];
+        format.decode(&frame, &mut expected);
+        assert!(panel.image() == expected, "{:?} region differs", format);
+
+        // The next full frame restores the full window
+        display.swap_buffers(&mut delay, &mut buffer).unwrap();
+        panel.process(log.take());
+        assert_eq!(panel.columns, (0, WIDTH - 1));
+        assert_eq!(panel.rows, (0, HEIGHT - 1));
+    }
+}
+
+#[test]
+fn invalid_regions_are_rejected() {
+    let (mut display, mut buffer, mut delay, log) = mock_display_in(PixelFormat::Rgb444);
+    display.init(&mut delay).unwrap();
+    log.take();
+    for region in [
+        Rect::new(0, 0, 0, 10),
+        Rect::new(200, 0, 42, 10),
+        Rect::new(0, 300, 10, 21),
+        Rect::new(3, 0, 10, 10),
+        Rect::new(u16::MAX - 1, 0, 4, 10),
+        Rect::new(0, u16::MAX, 10, 2),
+    ] {
+        assert_eq!(display.swap_region(&mut delay, region, &mut buffer), Err(DisplayError::Region), "{:?}", region);
+    }
+    assert!(log.take().is_empty());
+    assert_eq!(display.swap_region(&mut delay, Rect::new(2, 0, 10, 10), &mut buffer), Ok(()));
+}
+
+#[test]
+fn frames_can_be_streamed_in_strips() {
+    const STRIP_ROWS: usize = 16;
+    let format = PixelFormat::Rgb565;
+    let strip_size = format.bytes_for(WIDTH as usize * STRIP_ROWS);
+    let (mut display, mut strip, mut delay, log) = mock_display_with(format, strip_size);
+    display.init(&mut delay).unwrap();
+    let mut panel = St7789::default();
+    panel.process(log.take());
+    assert_eq!(panel.pixels_written, WIDTH as usize * HEIGHT as usize);
+
+    let uniforms = Uniforms::new(3.0, 0.03, 90, WIDTH, HEIGHT, InputState::default());
+    display.begin_frame(&mut delay).unwrap();
+    for first_row in (0..HEIGHT as usize).step_by(STRIP_ROWS) {
+        let rows = first_row..first_row + STRIP_ROWS;
+        render_strip(&Gradient, &uniforms, format, strip, WIDTH as usize, HEIGHT as usize, first_row, rows);
+        display.push_strip(&mut strip, STRIP_ROWS).unwrap();
+    }
+    assert_eq!(display.push_strip(&mut strip, 1), Err(DisplayError::FrameOverrun));
+
+    // One RAMWR, with the display selected from the first strip to the last
+    let events = log.take();
+    let first = events.iter().position(|e| matches!(e, Event::Write(w) if w.len() == strip_size)).unwrap();
+    assert!(!events[first..].contains(&Event::Pin(Pin::Cs, true)));
+    panel.process(events);
+    assert_eq!(panel.commands_since(0x2C), &[] as &[u8]);
+
+    let mut frame = vec![0u8; buffer_size(format)];
+    render_gradient(format, &mut frame);
+    let mut expected = vec![0u8; 3 * WIDTH as usize * HEIGHT as usize];
+    format.decode(&frame, &mut expected);
+    assert!(panel.image() == expected);
+}
+
+#[test]
+fn chained_strips_stream_back_to_back() {
+    const STRIP_ROWS: usize = 16;
+    let format = PixelFormat::Rgb565;
+    let strip_size = format.bytes_for(WIDTH as usize * STRIP_ROWS);
+    let (display, mut strip, mut delay, log) = mock_display_with(format, strip_size);
+    let mut display = display.with_spare_buffer(leak_buffer(strip_size));
+    display.init(&mut delay).unwrap();
+    let mut panel = St7789::default();
+    panel.process(log.take());
+    log.set_dma_chaining(true);
+
+    let uniforms = Uniforms::new(3.0, 0.03, 90, WIDTH, HEIGHT, InputState::default());
+    let mut strips = HashSet::new();
+    for _ in 0..2 {
+        let starts = log.dma_starts();
+        display.begin_frame(&mut delay).unwrap();
+        for first_row in (0..HEIGHT as usize).step_by(STRIP_ROWS) {
+            let rows = first_row..first_row + STRIP_ROWS;
+            render_strip(&Gradient, &uniforms, format, strip, WIDTH as usize, HEIGHT as usize, first_row, rows);
+            strips.insert(strip.as_ptr());
+            display.push_strip(&mut strip, STRIP_ROWS).unwrap();
+        }
+        // Only the first strip of each frame was started by the CPU
+        assert_eq!(log.dma_starts(), starts + 1);
+    }
+    // All three strips took turns
+    assert_eq!(strips.len(), 3);
+
+    panel.process(log.take());
+    let mut frame = vec![0u8; buffer_size(format)];
+    render_gradient(format, &mut frame);
+    let mut expected = vec![0u8; 3 * WIDTH as usize * HEIGHT as usize];
+    format.decode(&frame, &mut expected);
+    assert!(panel.image() == expected);
+}
+
+#[test]
+fn strips_need_a_frame_to_stream_into() {
+    let format = PixelFormat::Rgb565;
+    let (mut display, mut strip, mut delay, _log) = mock_display_with(format, format.bytes_for(WIDTH as usize * 8));
+    display.init(&mut delay).unwrap();
+    assert_eq!(display.push_strip(&mut strip, 8), Err(DisplayError::FrameOverrun));
+    display.begin_frame(&mut delay).unwrap();
+    assert_eq!(display.push_strip(&mut strip, 9), Err(DisplayError::BufferSize));
+    assert_eq!(display.push_strip(&mut strip, 8), Ok(()));
+    // Full frames cannot be swapped through strip buffers
+    assert_eq!(display.swap_buffers(&mut delay, &mut strip), Err(DisplayError::BufferSize));
+}
+
+#[test]
+fn swapping_waits_for_the_bus_instead_of_fixed_delays() {
+    let (mut display, mut buffer, mut delay, log) = mock_display();
+    display.init(&mut delay).unwrap();
+    let mut panel = St7789::default();
+    panel.process(log.take());
+
+    // Previously every frame blocked for 2 ms plus 200 ns of fixed delays
+    for _ in 0..3 {
+        display.swap_buffers(&mut delay, &mut buffer).unwrap();
+        assert_eq!(log.total_delay_ns(), 0);
+        let events = log.take();
+        // RAMWR has left the bus before DC switches to data
+        let dc_high = events.iter().position(|e| *e == Event::Pin(Pin::Dc, true)).unwrap();
+        assert_eq!(events[dc_high - 1], Event::Flush);
+        panel.process(events);
+    }
+    assert_eq!(panel.unflushed_pin_changes, 0);
+    assert_eq!(panel.commands_since(0x2C), &[] as &[u8]);
+}
+
+#[test]
+fn triple_buffering_never_waits_for_the_panel() {
+    let (display, mut buffer, mut delay, log) = mock_display();
+    let mut display = display.with_spare_buffer(leak_buffer(buffer_size(PixelFormat::Rgb666)));
+    display.init(&mut delay).unwrap();
+    let mut panel = St7789::default();
+    panel.process(log.take());
+    log.set_dma_busy(true);
+
+    // The first frame goes out at once, the next ones queue up behind it
+    let mut sent = Vec::new();
+    for shade in [0x40, 0x80, 0xC0] {
+        buffer.fill(shade);
+        sent.push(buffer.as_ptr());
+        display.queue_frame(&mut delay, &mut buffer).unwrap();
+        assert!(display.buffer_free());
+    }
+    // The newest frame replaced the one still waiting, which came back for rendering
+    assert_eq!(buffer.as_ptr(), sent[1]);
+
+    // Nothing happens until the transfer has actually finished
+    display.on_transfer_complete(&mut delay, true).unwrap();
+    panel.process(log.take());
+    assert!(panel.image().iter().all(|&c| c == 0x40));
+
+    log.set_dma_busy(false);
+    display.on_transfer_complete(&mut delay, true).unwrap();
+    display.on_transfer_complete(&mut delay, true).unwrap();
+    let events = log.take();
+    assert_eq!(events.last(), Some(&Event::Pin(Pin::Cs, true)));
+    panel.process(events);
+    assert!(panel.image().iter().all(|&c| c == 0xC0));
+    assert_eq!(panel.unflushed_pin_changes, 0);
+}
+
+#[test]
+fn the_completion_interrupt_is_raised_once_per_transfer() {
+    let (mut display, mut buffer, mut delay, log) = mock_display();
+    display.init(&mut delay).unwrap();
+    assert!(!display.acknowledge_interrupt());
+
+    log.set_dma_busy(true);
+    display.queue_frame(&mut delay, &mut buffer).unwrap();
+    assert!(!display.acknowledge_interrupt());
+    log.set_dma_busy(false);
+    assert!(display.acknowledge_interrupt());
+    assert!(!display.acknowledge_interrupt());
+    display.on_transfer_complete(&mut delay, true).unwrap();
+    assert!(!display.acknowledge_interrupt());
+}
+
+#[test]
+fn double_buffering_reports_when_no_buffer_is_free() {
+    let (mut display, mut buffer, mut delay, log) = mock_display();
+    display.init(&mut delay).unwrap();
+    log.set_dma_busy(true);
+    display.queue_frame(&mut delay, &mut buffer).unwrap();
+    assert!(!display.buffer_free());
+
+    let buffer_ptr = buffer.as_ptr();
+    assert_eq!(display.queue_frame(&mut delay, &mut buffer), Err(DisplayError::NoFreeBuffer));
+    assert_eq!(buffer.as_ptr(), buffer_ptr);
+
+    // Without automatic kickoff the completion only frees the buffer
+    log.set_dma_busy(false);
+    assert!(display.buffer_free());
+    log.take();
+    display.on_transfer_complete(&mut delay, false).unwrap();
+    assert_eq!(log.take(), [Event::Flush, Event::Pin(Pin::Cs, true)]);
+    assert_eq!(display.queue_frame(&mut delay, &mut buffer), Ok(()));
+    assert_ne!(buffer.as_ptr(), buffer_ptr);
+}
+
+#[test]
+fn sleep_pauses_frames_and_the_backlight_until_wake() {
+    let pwm = MockPwm::default();
+    let (display, mut buffer, mut delay, log) = mock_display();
+    let mut display = display
+        .with_spare_buffer(leak_buffer(buffer_size(PixelFormat::Rgb666)))
+        .with_backlight(pwm.clone());
+    display.init(&mut delay).unwrap();
+    display.set_brightness(180).unwrap();
+    let mut panel = St7789::default();
+    panel.process(log.take());
+
+    // A queued frame is dropped rather than sent into the sleeping panel
+    log.set_dma_busy(true);
+    display.queue_frame(&mut delay, &mut buffer).unwrap();
+    display.queue_frame(&mut delay, &mut buffer).unwrap();
+    log.set_dma_busy(false);
+    display.sleep(&mut delay).unwrap();
+    assert!(display.is_asleep());
+    assert_eq!(display.brightness(), 0);
+    assert_eq!(pwm.duties.borrow().last(), Some(&0));
+    assert_eq!(display.swap_buffers(&mut delay, &mut buffer), Err(DisplayError::Asleep));
+    assert_eq!(display.queue_frame(&mut delay, &mut buffer), Err(DisplayError::Asleep));
+    assert_eq!(display.begin_frame(&mut delay), Err(DisplayError::Asleep));
+    panel.process(log.take());
+    assert!(panel.sleeping);
+    assert_eq!(panel.pixels_written, 2 * WIDTH as usize * HEIGHT as usize);
+    let slept_at = panel.time_ns;
+
+    display.wake(&mut delay).unwrap();
+    panel.process(log.take());
+    assert!(!panel.sleeping);
+    assert!(panel.time_ns - slept_at >= 120_000_000);
+    assert_eq!(display.brightness(), 180);
+    display.swap_buffers(&mut delay, &mut buffer).unwrap();
+    assert_eq!(panel.unflushed_pin_changes, 0);
+}
+
+#[test]
+fn a_failed_sleep_restores_the_backlight() {
+    let pwm = MockPwm::default();
+    let (display, _buffer, mut delay, log) = mock_display();
+    let mut display = display.with_backlight(pwm.clone());
+    display.init(&mut delay).unwrap();
+    display.set_brightness(180).unwrap();
+    let lit = *pwm.duties.borrow().last().unwrap();
+
+    log.fail_next_spi_write();
+    assert!(display.sleep(&mut delay).is_err());
+    assert!(!display.is_asleep());
+    assert_eq!(display.brightness(), 180);
+    assert_eq!(pwm.duties.borrow().last(), Some(&lit));
+}
+
+#[test]
+fn frames_sent_while_the_display_is_off_show_up_when_it_is_on() {
+    let (mut display, mut buffer, mut delay, log) = mock_display();
+    display.init(&mut delay).unwrap();
+    display.display_off(&mut delay).unwrap();
+    assert!(!display.is_display_on());
+    buffer.fill(0xFC);
+    display.swap_buffers(&mut delay, &mut buffer).unwrap();
+
+    let mut panel = St7789::default();
+    panel.process(log.take());
+    assert!(!panel.display_on);
+    assert!(panel.image().iter().all(|&c| c == 0xFC));
+
+    display.display_on(&mut delay).unwrap();
+    panel.process(log.take());
+    assert!(panel.display_on);
+    assert!(display.is_display_on());
+}
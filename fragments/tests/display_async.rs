@@ -0,0 +1,109 @@
+//! Command-stream tests of the async display driver against an emulated ST7789
+
+#![cfg(feature = "async")]
+
+mod support;
+
+use fragments::color::PixelFormat;
+use fragments::display::{DisplayError, Orientation, Rotation};
+use fragments::framebuffer::{buffer_size, pack_region, Rect, HEIGHT, WIDTH};
+use fragments::panel::St7789;
+use fragments::shader::render_rows;
+use fragments::shaders::Gradient;
+use fragments::uniforms::{InputState, Uniforms};
+use support::st7789::St7789 as Emulator;
+use support::{block_on, mock_async_display, mock_async_panel_display};
+
+fn gradient_frame(format: PixelFormat) -> Vec<u8> {
+    let uniforms = Uniforms::new(3.0, 0.03, 90, WIDTH, HEIGHT, InputState::default());
+    let mut frame = vec![0u8; buffer_size(format)];
+    render_rows(&Gradient, &uniforms, format, &mut frame, WIDTH as usize, HEIGHT as usize, 0..HEIGHT as usize);
+    frame
+}
+
+fn decode(format: PixelFormat, frame: &[u8]) -> Vec<u8> {
+    let mut rgb = vec![0u8; 3 * WIDTH as usize * HEIGHT as usize];
+    format.decode(frame, &mut rgb);
+    rgb
+}
+
+#[test]
+fn init_configures_panel_like_the_blocking_driver() {
+    let (mut display, mut delay, log) = mock_async_display(PixelFormat::Rgb565);
+    block_on(display.init(&mut delay)).unwrap();
+
+    let mut panel = Emulator::default();
+    panel.process(log.take());
+    assert_eq!(panel.commands.first(), Some(&0x01));
+    assert!(!panel.sleeping);
+    assert!(panel.display_on);
+    assert!(panel.inverted);
+    assert_eq!(panel.colmod, 0x05);
+    assert_eq!(panel.columns, (0, WIDTH - 1));
+    assert_eq!(panel.rows, (0, HEIGHT - 1));
+    assert_eq!(panel.parameters[&0xE0].len(), 14);
+    assert_eq!(panel.pixels_written, WIDTH as usize * HEIGHT as usize);
+    assert!(panel.image().iter().all(|&c| c == 0));
+    assert_eq!(panel.unflushed_pin_changes, 0);
+}
+
+#[test]
+fn frames_and_regions_are_transmitted() {
+    let format = PixelFormat::Rgb565;
+    let (mut display, mut delay, log) = mock_async_display(format);
+    assert_eq!(block_on(display.swap_buffers(&gradient_frame(format))), Err(DisplayError::Uninitialized));
+    block_on(display.init(&mut delay)).unwrap();
+
+    let mut frame = gradient_frame(format);
+    block_on(display.swap_buffers(&frame)).unwrap();
+    assert_eq!(log.total_delay_ns(), 150_000_000 + 150_000_000 + 120_000_000 + 320_000_000);
+
+    let region = Rect::new(30, 40, 50, 20);
+    let stride = format.bytes_for(WIDTH as usize);
+    for row in region.y as usize..region.bottom() as usize {
+        let start = row * stride + format.bytes_for(region.x as usize);
+        frame[start..start + format.bytes_for(region.width as usize)].fill(0xFF);
+    }
+    let mut pixels = vec![0u8; format.bytes_for(region.area())];
+    pack_region(format, &frame, WIDTH as usize, region, &mut pixels);
+    block_on(display.swap_region(region, &pixels)).unwrap();
+    assert_eq!(block_on(display.swap_region(Rect::new(200, 0, 41, 1), &pixels)), Err(DisplayError::Region));
+    assert_eq!(block_on(display.swap_buffers(&pixels)), Err(DisplayError::BufferSize));
+
+    let mut panel = Emulator::default();
+    panel.process(log.take());
+    assert_eq!(panel.columns, (30, 79));
+    assert_eq!(panel.rows, (40, 59));
+    assert!(panel.image() == decode(format, &frame));
+    assert_eq!(panel.unflushed_pin_changes, 0);
+}
+
+#[test]
+fn orientation_is_applied() {
+    let (display, mut delay, log) = mock_async_display(PixelFormat::Rgb666);
+    let mut display = display.with_orientation(Orientation::new(Rotation::Deg90, false));
+    assert_eq!((display.width(), display.height()), (HEIGHT, WIDTH));
+    block_on(display.init(&mut delay)).unwrap();
+    block_on(display.set_orientation(Orientation::new(Rotation::Deg180, false))).unwrap();
+
+    let mut panel = Emulator::default();
+    panel.process(log.take());
+    assert_eq!(panel.parameters[&0x36], [0xC0]);
+    assert_eq!(panel.columns, (0, WIDTH - 1));
+    assert_eq!(panel.rows, (0, HEIGHT - 1));
+}
+
+#[test]
+fn regions_are_offset_into_the_frame_memory_of_smaller_panels() {
+    let format = PixelFormat::Rgb565;
+    let (display, mut delay, log) = mock_async_panel_display(St7789::LCD_135X240, format);
+    let mut display = display.with_orientation(Orientation::new(Rotation::Deg90, false));
+    assert_eq!((display.width(), display.height()), (240, 135));
+    block_on(display.init(&mut delay)).unwrap();
+    block_on(display.swap_region(Rect::new(10, 20, 30, 40), &[0xFF; 2 * 30 * 40])).unwrap();
+
+    let mut panel = Emulator::default();
+    panel.process(log.take());
+    assert_eq!(panel.columns, (50, 79));
+    assert_eq!(panel.rows, (73, 112));
+}
@@ -0,0 +1,107 @@
+//! Tests of embedded-graphics drawing onto frame buffers
+
+#![cfg(feature = "graphics")]
+
+use embedded_graphics::mono_font::ascii::FONT_6X10;
+use embedded_graphics::mono_font::MonoTextStyle;
+use embedded_graphics::pixelcolor::{Rgb565, Rgb666, Rgb888};
+use embedded_graphics::prelude::*;
+use embedded_graphics::primitives::{Circle, PrimitiveStyle, Rectangle};
+use embedded_graphics::text::Text;
+use fragments::color::{Color, PixelFormat};
+use fragments::framebuffer::{buffer_size, HEIGHT, WIDTH};
+use fragments::graphics::{FrameColor, Framebuffer};
+
+/// Overlay of text and shapes as drawn over shader output
+fn draw_overlay<C: FrameColor + RgbColor, D: DrawTarget<Color = C>>(target: &mut D) {
+    Rectangle::new(Point::new(10, 20), Size::new(50, 30))
+        .into_styled(PrimitiveStyle::with_fill(C::RED))
+        .draw(target)
+        .ok();
+    Circle::new(Point::new(100, 150), 41).into_styled(PrimitiveStyle::with_stroke(C::GREEN, 3)).draw(target).ok();
+    Text::new("60 fps", Point::new(4, 310), MonoTextStyle::new(&FONT_6X10, C::WHITE)).draw(target).ok();
+    // Partly off screen
+    Rectangle::new(Point::new(-5, -5), Size::new(10, 10))
+        .into_styled(PrimitiveStyle::with_fill(C::BLUE))
+        .draw(target)
+        .ok();
+}
+
+fn decode(format: PixelFormat, bytes: &[u8]) -> Vec<u8> {
+    let mut rgb = vec![0; 3 * WIDTH as usize * HEIGHT as usize];
+    format.decode(bytes, &mut rgb);
+    rgb
+}
+
+fn rgb_at(rgb: &[u8], x: usize, y: usize) -> &[u8] {
+    let index = 3 * (y * WIDTH as usize + x);
+    &rgb[index..index + 3]
+}
+
+fn check_overlay<C: FrameColor + RgbColor>() {
+    let format = C::FORMAT;
+    let mut bytes = vec![0; buffer_size(format)];
+    let mut frame = Framebuffer::<C>::new(&mut bytes, WIDTH, HEIGHT).unwrap();
+    assert_eq!(frame.size(), Size::new(WIDTH as u32, HEIGHT as u32));
+    draw_overlay(&mut frame);
+
+    // Shapes come out as the same bytes as shader colors
+    let rgb = decode(format, &bytes);
+    let expected = |color: Color| {
+        let mut encoded = [0; 6];
+        format.encode(&mut encoded[..format.bytes_for(2)], core::iter::repeat(color));
+        let mut pixel = [0; 6];
+        format.decode(&encoded[..format.bytes_for(2)], &mut pixel);
+        pixel[..3].to_vec()
+    };
+    assert_eq!(rgb_at(&rgb, 10, 20), expected(Color::new(1.0, 0.0, 0.0)), "{format:?}");
+    assert_eq!(rgb_at(&rgb, 59, 49), expected(Color::new(1.0, 0.0, 0.0)), "{format:?}");
+    assert_eq!(rgb_at(&rgb, 60, 49), [0, 0, 0], "{format:?}");
+    assert_eq!(rgb_at(&rgb, 4, 4), expected(Color::new(0.0, 0.0, 1.0)), "{format:?}");
+    assert_eq!(rgb_at(&rgb, 120, 151), expected(Color::new(0.0, 1.0, 0.0)), "{format:?}");
+    assert_eq!(rgb_at(&rgb, 120, 170), [0, 0, 0], "{format:?}");
+    let text = (302..312).flat_map(|y| (4..40).map(move |x| (x, y)));
+    assert!(text.clone().any(|(x, y)| rgb_at(&rgb, x, y) == expected(Color::new(1.0, 1.0, 1.0))), "{format:?}");
+
+    // Drawing strip by strip gives the same frame
+    const STRIP_ROWS: usize = 16;
+    let mut strips = vec![0; buffer_size(format)];
+    let stride = format.bytes_for(WIDTH as usize);
+    for (index, strip) in strips.chunks_mut(STRIP_ROWS * stride).enumerate() {
+        let first_row = (index * STRIP_ROWS) as u16;
+        let mut frame = Framebuffer::<C>::strip(strip, WIDTH, HEIGHT, first_row).unwrap();
+        assert_eq!(frame.rows(), first_row..first_row + STRIP_ROWS as u16);
+        draw_overlay(&mut frame);
+    }
+    assert!(strips == bytes, "{format:?} strips differ");
+}
+
+#[test]
+fn shapes_and_text_are_drawn_in_every_pixel_format() {
+    check_overlay::<Rgb888>();
+    check_overlay::<Rgb565>();
+    check_overlay::<Rgb666>();
+}
+
+#[test]
+fn rgb444_pixels_share_bytes_with_their_neighbours() {
+    let mut bytes = vec![0; PixelFormat::Rgb444.bytes_for(4 * 2)];
+    let mut frame = Framebuffer::<Rgb888>::new(&mut bytes, 4, 2).unwrap();
+    frame.clear(Rgb888::new(0x10, 0x20, 0x30)).unwrap();
+    frame.draw_iter([Pixel(Point::new(1, 0), Rgb888::new(0xA0, 0xB0, 0xC0)), Pixel(Point::new(2, 1), Rgb888::WHITE)]).unwrap();
+    assert_eq!(bytes, [0x12, 0x3A, 0xBC, 0x12, 0x31, 0x23, 0x12, 0x31, 0x23, 0xFF, 0xF1, 0x23]);
+}
+
+#[test]
+fn buffers_have_to_fit_the_frame() {
+    let mut bytes = vec![0; buffer_size(PixelFormat::Rgb565) - 1];
+    assert!(Framebuffer::<Rgb565>::new(&mut bytes, WIDTH, HEIGHT).is_none());
+    assert!(Framebuffer::<Rgb888>::strip(&mut bytes, 135, 240, 0).is_none());
+
+    // A strip of the last rows holds no more than the frame
+    let mut frame = Framebuffer::<Rgb565>::strip(&mut bytes, WIDTH, HEIGHT, HEIGHT - 4).unwrap();
+    assert_eq!(frame.rows(), HEIGHT - 4..HEIGHT);
+    frame.clear(Rgb565::WHITE).unwrap();
+    assert!(bytes[..PixelFormat::Rgb565.bytes_for(4 * WIDTH as usize)].iter().all(|&b| b == 0xFF));
+    assert!(bytes[PixelFormat::Rgb565.bytes_for(4 * WIDTH as usize)..].iter().all(|&b| b == 0));
+}
@@ -0,0 +1,193 @@
+//! Tests of the inter-core handoff, with a thread standing in for core 1
+
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use std::sync::Mutex;
+use std::thread;
+
+use fragments::color::PixelFormat;
+use fragments::handoff::{Handoff, Strip};
+use fragments::schedule::split_rows;
+use fragments::shader::{render_rows, render_strip};
+use fragments::shaders::Gradient;
+use fragments::uniforms::Uniforms;
+
+const WIDTH: usize = 40;
+const HEIGHT: usize = 30;
+const FORMAT: PixelFormat = PixelFormat::Rgb565;
+
+fn uniforms(frame: u32) -> Uniforms {
+    Uniforms { time: frame as f32 / 60.0, frame, ..Uniforms::default() }
+}
+
+#[test]
+fn strips_split_into_disjoint_rows() {
+    let stride = FORMAT.bytes_for(WIDTH);
+    let mut bytes = vec![0; 10 * stride];
+    let (top, bottom) = Strip::new(&mut bytes, 20).split_at_row(4, stride);
+    assert_eq!((top.rows(stride), bottom.rows(stride)), (20..24, 24..30));
+    let (all, none) = Strip::new(&mut bytes, 20).split_at_row(12, stride);
+    assert_eq!((all.rows(stride), none.rows(stride)), (20..30, 30..30));
+}
+
+#[test]
+fn two_cores_render_the_same_frames_as_one() {
+    const FRAMES: u32 = 20;
+    const STRIP_ROWS: usize = 8;
+    let stride = FORMAT.bytes_for(WIDTH);
+    let handoff = Handoff::<Uniforms>::new();
+
+    thread::scope(|scope| {
+        scope.spawn(|| {
+            for _ in 0..FRAMES * HEIGHT.div_ceil(STRIP_ROWS) as u32 {
+                handoff.serve(|strip, uniforms| {
+                    let rows = strip.rows(stride);
+                    render_strip(&Gradient, uniforms, FORMAT, strip.bytes, WIDTH, HEIGHT, strip.first_row, rows);
+                });
+            }
+        });
+
+        let mut strip = vec![0; STRIP_ROWS * stride];
+        for frame in 0..FRAMES {
+            let uniforms = uniforms(frame);
+            let mut rendered = Vec::new();
+            for first_row in (0..HEIGHT).step_by(STRIP_ROWS) {
+                let rows = STRIP_ROWS.min(HEIGHT - first_row);
+                let own = split_rows(rows, 2, 0).len();
+                let (top, bottom) = Strip::new(&mut strip[..rows * stride], first_row).split_at_row(own, stride);
+                let local_rows = handoff.run(bottom, uniforms, || {
+                    let rows = top.rows(stride);
+                    render_strip(&Gradient, &uniforms, FORMAT, top.bytes, WIDTH, HEIGHT, top.first_row, rows.clone());
+                    rows
+                });
+                assert_eq!(local_rows, first_row..first_row + own);
+                rendered.extend_from_slice(&strip[..rows * stride]);
+            }
+
+            let mut expected = vec![0; HEIGHT * stride];
+            render_rows(&Gradient, &uniforms, FORMAT, &mut expected, WIDTH, HEIGHT, 0..HEIGHT);
+            assert!(rendered == expected, "frame {frame} differs");
+        }
+    });
+}
+
+#[test]
+fn the_strip_comes_back_even_if_either_side_panics() {
+    let handoff = Handoff::<u32>::new();
+    let mut bytes = [0u8; 8];
+
+    thread::scope(|scope| {
+        scope.spawn(|| {
+            let _ = catch_unwind(AssertUnwindSafe(|| handoff.serve(|_, _| panic!("serving core failed"))));
+            handoff.serve(|strip, &value| strip.bytes.fill(value as u8));
+        });
+
+        // The panic on the serving side still ends the exchange
+        handoff.run(Strip::new(&mut bytes, 0), 1, || ());
+        let result = catch_unwind(AssertUnwindSafe(|| handoff.run(Strip::new(&mut bytes, 0), 7, || panic!("lending core failed"))));
+        assert!(result.is_err());
+    });
+    // The lending side waited for the second exchange before unwinding
+    assert_eq!(bytes, [7; 8]);
+}
+
+#[test]
+fn shared_bands_are_rendered_once_by_whichever_core_is_free() {
+    const BAND_ROWS: usize = 4;
+    let stride = FORMAT.bytes_for(WIDTH);
+    let handoff = Handoff::<Uniforms>::new();
+    let uniforms = uniforms(3);
+    let taken = Mutex::new(Vec::new());
+    let serving_core_started = AtomicBool::new(false);
+    let mut frame = vec![0; HEIGHT * stride];
+
+    thread::scope(|scope| {
+        scope.spawn(|| {
+            handoff.serve(|strip, uniforms| {
+                serving_core_started.store(true, Ordering::Release);
+                taken.lock().unwrap().push((1, strip.first_row));
+                let rows = strip.rows(stride);
+                render_strip(&Gradient, uniforms, FORMAT, strip.bytes, WIDTH, HEIGHT, strip.first_row, rows);
+            });
+        });
+
+        let mut first = true;
+        handoff.share(Strip::new(&mut frame, 0), BAND_ROWS, stride, uniforms, |strip, uniforms| {
+            // Stall on the first band so that the other core has to help out
+            while first && !serving_core_started.load(Ordering::Acquire) {
+                std::hint::spin_loop();
+            }
+            first = false;
+            taken.lock().unwrap().push((0, strip.first_row));
+            let rows = strip.rows(stride);
+            render_strip(&Gradient, uniforms, FORMAT, strip.bytes, WIDTH, HEIGHT, strip.first_row, rows);
+        });
+    });
+
+    let mut taken = taken.into_inner().unwrap();
+    assert!(taken.iter().any(|&(core, _)| core == 0) && taken.iter().any(|&(core, _)| core == 1));
+    taken.sort_by_key(|&(_, row)| row);
+    let rows: Vec<usize> = taken.iter().map(|&(_, row)| row).collect();
+    assert_eq!(rows, (0..HEIGHT).step_by(BAND_ROWS).collect::<Vec<_>>());
+
+    let mut expected = vec![0; HEIGHT * stride];
+    render_rows(&Gradient, &uniforms, FORMAT, &mut expected, WIDTH, HEIGHT, 0..HEIGHT);
+    assert!(frame == expected);
+}
+
+#[test]
+fn sharing_does_not_wait_for_a_core_that_never_shows_up() {
+    let handoff = Handoff::<()>::new();
+    let mut bytes = [0u8; 10];
+    let mut bands = Vec::new();
+    handoff.share(Strip::new(&mut bytes, 5), 2, 2, (), |strip, _| {
+        strip.bytes.fill(strip.first_row as u8);
+        bands.push(strip.first_row);
+    });
+    assert_eq!(bands, [5, 7, 9]);
+    assert_eq!(bytes, [5, 5, 5, 5, 7, 7, 7, 7, 9, 9]);
+
+    // The slot is free again
+    handoff.share(Strip::new(&mut bytes, 0), 5, 2, (), |strip, _| strip.bytes.fill(0));
+    assert_eq!(bytes, [0; 10]);
+}
+
+static SERVER_STUCK: AtomicBool = AtomicBool::new(false);
+
+/// Stands in for stopping the serving core: the stuck thread never comes back
+fn stop_stuck_server() -> bool {
+    SERVER_STUCK.load(Ordering::Acquire)
+}
+
+static SUPERVISED: Handoff<u8> = unsafe { Handoff::with_recovery(std::hint::spin_loop, || {}, stop_stuck_server) };
+
+#[test]
+fn the_lending_core_takes_the_strip_back_from_a_stopped_core() {
+    static SERVED: AtomicU32 = AtomicU32::new(0);
+    thread::spawn(|| loop {
+        SUPERVISED.serve(|strip, &value| {
+            if SERVED.fetch_add(1, Ordering::Relaxed) == 1 {
+                // Hang without touching the strip again
+                SERVER_STUCK.store(true, Ordering::Release);
+                loop {
+                    thread::park();
+                }
+            }
+            strip.bytes.fill(value);
+        });
+    });
+
+    // Leaked, as the stuck thread keeps its borrow
+    let bytes: &'static mut [u8] = Vec::leak(vec![0; 6]);
+    SUPERVISED.run(Strip::new(bytes, 0), 1, || ());
+    assert_eq!(bytes, [1; 6]);
+    let (own, lent) = Strip::new(bytes, 0).split_at_row(3, 1);
+    SUPERVISED.run(lent, 2, || own.bytes.fill(2));
+    assert_eq!(bytes, [2, 2, 2, 1, 1, 1]);
+
+    // Later exchanges end without the stopped core
+    SUPERVISED.share(Strip::new(bytes, 0), 2, 1, 3, |strip, &value| strip.bytes.fill(value));
+    assert_eq!(bytes, [3; 6]);
+    SUPERVISED.run(Strip::new(bytes, 0), 4, || ());
+    assert_eq!(SERVED.load(Ordering::Relaxed), 2);
+}
@@ -0,0 +1,193 @@
+//! Tests of the panel controllers against the emulated controller frame memory
+
+mod support;
+
+use fragments::color::PixelFormat;
+use fragments::display::{DisplayError, Orientation, Rotation};
+use fragments::panel::{Gc9a01, Ili9341, PanelController, St7789};
+use fragments::panel_config::{Avcl, Avdd, Gamma, InversionMode, PanelConfig, Vds, Vgh, Vgl};
+use support::st7789::{St7789 as Emulator, PANEL_HEIGHT, PANEL_WIDTH};
+use support::mock_panel_display;
+
+const ROTATIONS: [Rotation; 4] = [Rotation::Deg0, Rotation::Deg90, Rotation::Deg180, Rotation::Deg270];
+
+/// Columns and rows of frame memory holding lit pixels, as `(left, top, right, bottom)`
+fn lit_area(panel: &Emulator) -> Option<(usize, usize, usize, usize)> {
+    let image = panel.image();
+    let mut area: Option<(usize, usize, usize, usize)> = None;
+    for y in 0..PANEL_HEIGHT {
+        for x in 0..PANEL_WIDTH {
+            let index = 3 * (y * PANEL_WIDTH + x);
+            if image[index..index + 3].iter().all(|&c| c == 0) {
+                continue;
+            }
+            area = Some(match area {
+                None => (x, y, x + 1, y + 1),
+                Some((l, t, r, b)) => (l.min(x), t.min(y), r.max(x + 1), b.max(y + 1)),
+            });
+        }
+    }
+    area
+}
+
+#[test]
+fn frames_fill_the_visible_area_of_st7789_modules_in_every_orientation() {
+    let modules = [
+        (St7789::LCD_240X320, (0, 0, 240, 320)),
+        (St7789::LCD_240X240, (0, 0, 240, 240)),
+        (St7789::LCD_135X240, (52, 40, 187, 280)),
+    ];
+    for (module, visible) in modules {
+        for rotation in ROTATIONS {
+            let orientation = Orientation::new(rotation, false);
+            let (display, mut buffer, mut delay, log) = mock_panel_display(module, PixelFormat::Rgb565);
+            let mut display = display.with_orientation(orientation);
+            display.init(&mut delay).unwrap();
+            buffer.fill(0xFF);
+            display.swap_buffers(&mut delay, &mut buffer).unwrap();
+
+            let mut panel = Emulator::default();
+            panel.process(log.take());
+            assert_eq!(lit_area(&panel), Some(visible), "{module:?} {rotation:?}");
+            assert_eq!(panel.pixels_written, 2 * module.size().0 as usize * module.size().1 as usize);
+        }
+    }
+}
+
+#[test]
+fn small_st7789_modules_are_addressed_at_their_ram_offsets() {
+    // Column and row of the first pixel per rotation, as in the vendor's examples
+    let expected = [(52, 40), (40, 53), (53, 40), (40, 52)];
+    for (rotation, (x, y)) in ROTATIONS.into_iter().zip(expected) {
+        let orientation = Orientation::new(rotation, false);
+        assert_eq!(St7789::LCD_135X240.window_offset(orientation), (x, y), "{rotation:?}");
+    }
+    // The square module sits at the start of the 320 rows of frame memory
+    let flipped = Orientation::new(Rotation::Deg180, false);
+    assert_eq!(St7789::LCD_240X240.window_offset(flipped), (0, 80));
+}
+
+#[test]
+fn rgb444_needs_an_even_width() {
+    let (mut display, _buffer, mut delay, log) = mock_panel_display(St7789::LCD_135X240, PixelFormat::Rgb565);
+    display = display.with_pixel_format(PixelFormat::Rgb444);
+    assert_eq!(display.init(&mut delay), Err(DisplayError::UnsupportedFormat));
+    assert!(log.take().is_empty());
+    assert_eq!(St7789::LCD_240X240.colmod(PixelFormat::Rgb444), Some(0x03));
+}
+
+#[test]
+fn ili9341_is_driven_in_landscape_with_its_own_init_sequence() {
+    let (display, mut buffer, mut delay, log) = mock_panel_display(Ili9341, PixelFormat::Rgb565);
+    let mut display = display.with_orientation(Orientation::new(Rotation::Deg90, false));
+    assert_eq!((display.width(), display.height()), (320, 240));
+    display.init(&mut delay).unwrap();
+    buffer.fill(0xFF);
+    display.swap_buffers(&mut delay, &mut buffer).unwrap();
+
+    let mut panel = Emulator::default();
+    panel.process(log.take());
+    assert_eq!(panel.colmod, 0x55);
+    // BGR subpixel order on top of the rotation
+    assert_eq!(panel.madctl, 0x68);
+    assert!(!panel.inverted);
+    for command in [0xCF, 0xED, 0xE8, 0xCB, 0xC0, 0xC1, 0xC5, 0xC7] {
+        assert!(panel.commands.contains(&command), "{command:#04x}");
+    }
+    assert_eq!(panel.parameters[&0xE0].len(), 15);
+    assert_eq!((panel.columns, panel.rows), ((0, 319), (0, 239)));
+    assert_eq!(lit_area(&panel), Some((0, 0, 240, 320)));
+
+    let (mut display, _buffer, mut delay, _log) = mock_panel_display(Ili9341, PixelFormat::Rgb444);
+    assert_eq!(display.init(&mut delay), Err(DisplayError::UnsupportedFormat));
+}
+
+#[test]
+fn gc9a01_is_unlocked_and_inverted() {
+    let (mut display, mut buffer, mut delay, log) = mock_panel_display(Gc9a01, PixelFormat::Rgb666);
+    display.init(&mut delay).unwrap();
+    buffer.fill(0xFF);
+    display.swap_buffers(&mut delay, &mut buffer).unwrap();
+
+    let mut panel = Emulator::default();
+    panel.process(log.take());
+    // The inter-register enable commands come first
+    let vendor = panel.commands_since(0x36);
+    assert_eq!(&vendor[..3], &[0xEF, 0xEB, 0xFE]);
+    assert!(panel.inverted);
+    assert_eq!(panel.colmod, 0x06);
+    assert_eq!(panel.madctl, 0x08);
+    assert_eq!(panel.parameters[&0x62].len(), 12);
+    assert_eq!(lit_area(&panel), Some((0, 0, 240, 240)));
+    assert_eq!(panel.unflushed_pin_changes, 0);
+}
+
+#[test]
+fn st7789_defaults_to_the_reset_values_and_the_waveshare_gamma_curves() {
+    let (mut display, _buffer, mut delay, log) = mock_panel_display(St7789::LCD_240X320, PixelFormat::Rgb666);
+    display.init(&mut delay).unwrap();
+
+    let mut panel = Emulator::default();
+    panel.process(log.take());
+    assert!(panel.inverted);
+    assert_eq!(panel.parameters[&0xB2], [0x0C, 0x0C, 0x00, 0x33, 0x33]);
+    assert_eq!(panel.parameters[&0xB7], [0x35]);
+    assert_eq!(panel.parameters[&0xBB], [0x20]);
+    assert_eq!(panel.parameters[&0xC0], [0x2C]);
+    assert_eq!(panel.parameters[&0xC2], [0x01, 0xFF]);
+    assert_eq!(panel.parameters[&0xC3], [0x0B]);
+    assert_eq!(panel.parameters[&0xC4], [0x20]);
+    assert_eq!(panel.parameters[&0xC6], [0x0F]);
+    assert_eq!(panel.parameters[&0xD0], [0xA4, 0xA1]);
+    assert_eq!(panel.parameters[&0xE0], [0xD0, 0x08, 0x11, 0x08, 0x0C, 0x15, 0x39, 0x33, 0x50, 0x36, 0x13, 0x14, 0x29, 0x2D]);
+    assert_eq!(panel.parameters[&0xE1], [0xD0, 0x08, 0x10, 0x08, 0x06, 0x06, 0x39, 0x44, 0x51, 0x0B, 0x16, 0x14, 0x2F, 0x31]);
+    assert_eq!(St7789::LCD_240X320.config().frame_rate_hz(), 59);
+}
+
+#[test]
+fn panel_config_tunes_frame_rate_voltages_and_gamma() {
+    let mut positive = Gamma::POSITIVE;
+    positive.v63 = 0x0A;
+    positive.j0 = 2;
+    let config = PanelConfig::new()
+        .with_inverted_colors(false)
+        .with_porch(8, 8)
+        .with_frame_rate(50)
+        .with_inversion_mode(InversionMode::Column)
+        .with_gate_voltages(Vgh::V14_97, Vgl::Minus7_16)
+        .with_vcom(1200)
+        .with_gvdd(4500)
+        .with_vdv(-100)
+        .with_power(Avdd::V6_4, Avcl::Minus5_0, Vds::V2_51)
+        .with_gamma(positive, Gamma::NEGATIVE);
+    assert_eq!(config.frame_rate_hz(), 49);
+
+    let module = St7789::LCD_240X320.with_config(config);
+    let (mut display, _buffer, mut delay, log) = mock_panel_display(module, PixelFormat::Rgb565);
+    display.init(&mut delay).unwrap();
+
+    let mut panel = Emulator::default();
+    panel.process(log.take());
+    assert!(!panel.inverted);
+    assert_eq!(panel.parameters[&0xB2], [0x08, 0x08, 0x00, 0x33, 0x33]);
+    assert_eq!(panel.parameters[&0xB7], [0x70]);
+    assert_eq!(panel.parameters[&0xBB], [0x2C]);
+    assert_eq!(panel.parameters[&0xC3], [0x13]);
+    assert_eq!(panel.parameters[&0xC4], [0x1C]);
+    assert_eq!(panel.parameters[&0xC6], [0xE0 | 0x16]);
+    assert_eq!(panel.parameters[&0xD0], [0xA4, 0x33]);
+    assert_eq!(panel.parameters[&0xE0][0], 0xA0);
+    assert_eq!(panel.parameters[&0xE0][5], 0x25);
+
+    // Rates beyond the panel's range settle at its limits
+    assert_eq!(PanelConfig::new().with_frame_rate(10).frame_rate_hz(), 38);
+    assert_eq!(PanelConfig::new().with_frame_rate(500).frame_rate_hz(), 116);
+}
+
+#[test]
+fn gamma_curves_round_trip_through_their_register_bytes() {
+    let bytes = [0xD0, 0x08, 0x11, 0x08, 0x0C, 0x15, 0x39, 0x33, 0x50, 0x36, 0x13, 0x14, 0x29, 0x2D];
+    assert_eq!(Gamma::from_bytes(bytes).to_bytes(), bytes);
+    assert_eq!(Gamma::POSITIVE.v63, 0x0D);
+    assert_eq!(Gamma::POSITIVE.v36, 3);
+}
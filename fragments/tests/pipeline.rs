@@ -0,0 +1,107 @@
+//! Tests of pipelined rendering, with a thread standing in for core 1
+
+use std::collections::HashSet;
+use std::thread;
+
+use fragments::color::PixelFormat;
+use fragments::handoff::Handoff;
+use fragments::pipeline::Pipeline;
+use fragments::shader::render_rows;
+use fragments::shaders::Gradient;
+use fragments::uniforms::Uniforms;
+
+const WIDTH: usize = 24;
+const HEIGHT: usize = 16;
+const FORMAT: PixelFormat = PixelFormat::Rgb565;
+const FRAME_SIZE: usize = HEIGHT * WIDTH * 2;
+
+fn uniforms(frame: u32) -> Uniforms {
+    Uniforms { time: frame as f32 / 60.0, frame, ..Uniforms::default() }
+}
+
+/// Post-processing pass standing in for an overlay: a bar whose length depends on the frame
+fn post_process(buffer: &mut [u8], uniforms: &Uniforms) {
+    buffer[..2 * (uniforms.frame as usize % WIDTH + 1)].fill(0xFF);
+}
+
+#[test]
+fn frames_come_out_finished_one_step_later() {
+    const FRAMES: u32 = 12;
+    let handoff = Handoff::<Uniforms>::new();
+    let (mut a, mut b, mut c) = (vec![0; FRAME_SIZE], vec![0; FRAME_SIZE], vec![0; FRAME_SIZE]);
+
+    thread::scope(|scope| {
+        scope.spawn(|| {
+            for _ in 0..FRAMES {
+                handoff.serve(|strip, uniforms| render_rows(&Gradient, uniforms, FORMAT, strip.bytes, WIDTH, HEIGHT, 0..HEIGHT));
+            }
+        });
+
+        let mut pipeline = Pipeline::new(&mut a, &mut b);
+        // Buffer held by the display, swapped for every finished frame as with `queue_frame`
+        let mut displayed: &mut [u8] = &mut c;
+        let mut buffers = HashSet::new();
+        assert!(pipeline.step(&handoff, uniforms(0), |_, _| unreachable!()).is_none());
+        for frame in 1..FRAMES {
+            let finished = pipeline.step(&handoff, uniforms(frame), |shaded, uniforms| {
+                post_process(shaded, uniforms);
+                buffers.insert(shaded.as_ptr());
+                std::mem::swap(shaded, &mut displayed);
+                uniforms.frame
+            });
+            assert_eq!(finished, Some(frame - 1));
+            assert_eq!(pipeline.pending().map(|uniforms| uniforms.frame), Some(frame));
+
+            let mut expected = vec![0; FRAME_SIZE];
+            render_rows(&Gradient, &uniforms(frame - 1), FORMAT, &mut expected, WIDTH, HEIGHT, 0..HEIGHT);
+            post_process(&mut expected, &uniforms(frame - 1));
+            assert!(displayed == expected, "frame {} differs", frame - 1);
+        }
+        // All three buffers took turns
+        assert_eq!(buffers.len(), 3);
+    });
+}
+
+#[test]
+fn a_discarded_frame_is_not_finished() {
+    let handoff = Handoff::<u8>::new();
+    let (mut a, mut b) = ([0u8; 4], [0u8; 4]);
+
+    thread::scope(|scope| {
+        scope.spawn(|| {
+            for _ in 0..3 {
+                handoff.serve(|strip, &value| strip.bytes.fill(value));
+            }
+        });
+
+        let mut pipeline = Pipeline::new(&mut a, &mut b);
+        pipeline.step(&handoff, 1, |_, _| ());
+        assert_eq!(pipeline.discard(), Some(1));
+        assert!(pipeline.step(&handoff, 2, |_, _| unreachable!()).is_none());
+        let finished = pipeline.step(&handoff, 3, |shaded, &value| {
+            assert_eq!(**shaded, [2; 4]);
+            value
+        });
+        assert_eq!(finished, Some(2));
+    });
+}
+
+#[test]
+fn shading_locally_keeps_the_frame_order() {
+    let handoff = Handoff::<u8>::new();
+    let (mut a, mut b) = ([0u8; 4], [0u8; 4]);
+    let mut finished = Vec::new();
+
+    thread::scope(|scope| {
+        scope.spawn(|| handoff.serve(|strip, &value| strip.bytes.fill(value)));
+
+        let mut pipeline = Pipeline::new(&mut a, &mut b);
+        pipeline.step(&handoff, 1, |_, _| ());
+        // The serving core is gone from here on
+        for value in 2..5 {
+            let mut finish = |shaded: &mut &mut [u8], &value: &u8| finished.push((value, shaded[0]));
+            pipeline.step_local(value, |strip, &value| strip.bytes.fill(value), &mut finish);
+        }
+    });
+    assert_eq!(finished, [(1, 1), (2, 2), (3, 3)]);
+}
@@ -0,0 +1,26 @@
+//! Tests of the idle power policy
+
+use fragments::power::{IdlePolicy, PowerAction};
+use fragments::uniforms::InputState;
+
+#[test]
+fn idle_policy_sleeps_after_the_timeout_and_wakes_on_input() {
+    let released = InputState::default();
+    let pressed = InputState::from_pressed([false, false, true, false]);
+    let mut policy = IdlePolicy::new(1_000, 500);
+
+    assert_eq!(policy.update(1_400, released), None);
+    // Input restarts the timeout
+    assert_eq!(policy.update(1_450, pressed), None);
+    assert_eq!(policy.update(2_449, released), None);
+    assert_eq!(policy.update(2_450, released), Some(PowerAction::Sleep));
+    assert!(policy.is_idle());
+    assert_eq!(policy.update(9_000, released), None);
+
+    assert_eq!(policy.update(9_100, pressed), Some(PowerAction::Wake));
+    assert!(!policy.is_idle());
+    // Holding the button keeps the display awake
+    assert_eq!(policy.update(20_000, pressed), None);
+    assert_eq!(policy.update(20_999, released), None);
+    assert_eq!(policy.update(21_000, released), Some(PowerAction::Sleep));
+}
@@ -0,0 +1,91 @@
+//! Tests of the work distribution statistics
+
+use fragments::schedule::{split_rows, AdaptiveSplit, CoreLoad, CoreStats, LoadMonitor};
+
+#[test]
+fn static_splits_cover_every_row_once() {
+    assert_eq!([split_rows(321, 2, 0), split_rows(321, 2, 1)], [0..160, 160..321]);
+}
+
+#[test]
+fn core_stats_sum_up_windows_of_work() {
+    let stats = CoreStats::new();
+    let mut monitor = LoadMonitor::new(1_000_000, 0);
+    for _ in 0..10 {
+        stats.record(0, 8, 60_000);
+        stats.record(1, 8, 20_000);
+    }
+    stats.record(1, 8, 20_000);
+    assert!(!monitor.update(999_999, &stats));
+    assert_eq!(monitor.loads(), [CoreLoad::default(); 2]);
+
+    assert!(monitor.update(1_000_000, &stats));
+    assert_eq!(monitor.loads()[0], CoreLoad { bands: 10, rows: 80, busy_us: 600_000 });
+    assert_eq!(monitor.loads()[1], CoreLoad { bands: 11, rows: 88, busy_us: 220_000 });
+    assert_eq!((monitor.utilization_percent(0), monitor.utilization_percent(1)), (60, 22));
+
+    // The counters start over with the next window
+    stats.record(1, 8, 3_000_000);
+    assert!(monitor.update(2_500_000, &stats));
+    assert_eq!(monitor.loads()[0], CoreLoad::default());
+    assert_eq!(monitor.utilization_percent(1), 100);
+    assert_eq!(CoreLoad::default().utilization_percent(0), 0);
+}
+
+/// Let `split` adapt to cores that take `cost_us` per row, with core 0 also losing
+/// `overhead_us` per frame to interrupts, for `frames` frames of `rows` rows
+fn adapt(split: &mut AdaptiveSplit, rows: usize, cost_us: [u64; 2], overhead_us: u64, frames: usize) -> [u64; 2] {
+    let mut busy_us = [0; 2];
+    for _ in 0..frames {
+        let core0 = split.core0_rows(rows);
+        let parts = [core0, rows - core0];
+        busy_us = [parts[0] as u64 * cost_us[0] + overhead_us, parts[1] as u64 * cost_us[1]];
+        split.update(parts, busy_us);
+    }
+    busy_us
+}
+
+#[test]
+fn adaptive_splits_even_out_the_render_times() {
+    let mut split = AdaptiveSplit::new();
+    assert_eq!(split.core0_rows(320), 160);
+
+    // Equal cores stay at an even split
+    adapt(&mut split, 320, [50, 50], 0, 10);
+    assert_eq!(split.core0_rows(320), 160);
+
+    // Core 0 loses time to the display and gets fewer rows until both finish together
+    let [core0_us, core1_us] = adapt(&mut split, 320, [50, 50], 4_000, 20);
+    assert!(split.core0_rows(320) < 130, "{split:?}");
+    assert!(core0_us.abs_diff(core1_us) <= 100, "{core0_us} vs {core1_us}");
+
+    // Rows that cost three times as much on core 1, e.g. a raymarched object in its half
+    let mut split = AdaptiveSplit::new();
+    adapt(&mut split, 320, [20, 60], 0, 20);
+    assert_eq!(split.core0_rows(320), 240);
+    // The same share applies to strips
+    assert_eq!(split.core0_rows(16), 12);
+}
+
+#[test]
+fn adaptive_splits_keep_both_cores_measurable() {
+    let mut split = AdaptiveSplit::new();
+    adapt(&mut split, 320, [1, 1_000], 0, 30);
+    assert_eq!(split.share(), 1.0 - AdaptiveSplit::MIN_SHARE);
+    assert_eq!(split.core0_rows(320), 300);
+
+    // Frames without a measurement leave the split alone
+    let before = split;
+    split.update([320, 0], [1_000, 0]);
+    split.update([160, 160], [0, 1_000]);
+    assert_eq!(split, before);
+}
+
+#[test]
+fn core_stats_keep_the_latest_band_time() {
+    let stats = CoreStats::new();
+    stats.record(1, 160, 9_000);
+    stats.record(1, 160, 7_000);
+    stats.take();
+    assert_eq!((stats.last_busy_us(0), stats.last_busy_us(1)), (0, 7_000));
+}
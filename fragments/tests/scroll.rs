@@ -0,0 +1,182 @@
+//! Tests of hardware vertical scrolling against the emulated controller
+
+mod support;
+
+use fragments::color::PixelFormat;
+use fragments::display::{DisplayError, Orientation, Rotation};
+use fragments::framebuffer::{HEIGHT, WIDTH};
+use fragments::panel::St7789;
+use fragments::scroll::{ScrollArea, ScrollLog};
+use support::st7789::{St7789 as Emulator, PANEL_HEIGHT, PANEL_WIDTH};
+use support::{mock_display_in, mock_panel_display};
+
+const ROW_BYTES: usize = 3 * PANEL_WIDTH;
+
+/// RGB666 color that tells frame row `row` apart from all others
+fn row_color(row: usize) -> [u8; 3] {
+    [((row & 0x3F) << 2) as u8, (((row >> 6) & 0x3F) << 2) as u8, 0xFC]
+}
+
+fn row_of(image: &[u8], row: usize) -> &[u8] {
+    &image[row * ROW_BYTES..(row + 1) * ROW_BYTES]
+}
+
+#[test]
+fn scrolled_rows_show_up_in_frame_coordinates_in_every_portrait_orientation() {
+    let area = ScrollArea::new(16, 288, 16);
+    for orientation in [
+        Orientation::new(Rotation::Deg0, false),
+        Orientation::new(Rotation::Deg180, false),
+        Orientation::new(Rotation::Deg0, true),
+    ] {
+        let (display, mut buffer, mut delay, log) = mock_display_in(PixelFormat::Rgb666);
+        let mut display = display.with_orientation(orientation);
+        display.init(&mut delay).unwrap();
+        for (row, pixels) in buffer.chunks_exact_mut(ROW_BYTES).enumerate() {
+            for pixel in pixels.chunks_exact_mut(3) {
+                pixel.copy_from_slice(&row_color(row));
+            }
+        }
+        display.swap_buffers(&mut delay, &mut buffer).unwrap();
+        display.define_scroll_area(&mut delay, area).unwrap();
+        display.scroll_to(&mut delay, 40).unwrap();
+        assert_eq!((display.scroll_area(), display.scroll_offset()), (Some(area), 40));
+
+        let mut panel = Emulator::default();
+        panel.process(log.take());
+        assert!(panel.scrolling);
+        let screen = panel.screen();
+        let physical = |row: usize| if orientation.rotation == Rotation::Deg180 { PANEL_HEIGHT - 1 - row } else { row };
+        for row in 0..HEIGHT as usize {
+            let source = match row as u16 {
+                r if r < area.top_fixed || r >= area.top_fixed + area.scroll_height => row,
+                r => area.source_row(r - area.top_fixed, 40).unwrap() as usize,
+            };
+            let color = row_color(source);
+            assert!(
+                row_of(&screen, physical(row)).chunks_exact(3).all(|pixel| pixel == color),
+                "{orientation:?} row {row} does not show row {source}"
+            );
+        }
+    }
+}
+
+#[test]
+fn scroll_areas_count_rows_outside_the_visible_area_as_fixed() {
+    let (mut display, _buffer, mut delay, log) = mock_display_in(PixelFormat::Rgb565);
+    display.init(&mut delay).unwrap();
+    display.define_scroll_area(&mut delay, ScrollArea::new(16, 288, 16)).unwrap();
+    let mut panel = Emulator::default();
+    panel.process(log.take());
+    assert_eq!(panel.parameters[&0x33], [0, 16, 0x01, 0x20, 0, 16]);
+    assert_eq!(panel.parameters[&0x37], [0, 16]);
+
+    let modules = [
+        (St7789::LCD_135X240, Rotation::Deg0, (40, 240, 40)),
+        (St7789::LCD_240X240, Rotation::Deg0, (0, 240, 80)),
+        (St7789::LCD_240X240, Rotation::Deg180, (0, 240, 80)),
+    ];
+    for (module, rotation, rows) in modules {
+        let (display, _buffer, mut delay, log) = mock_panel_display(module, PixelFormat::Rgb565);
+        let mut display = display.with_orientation(Orientation::new(rotation, false));
+        display.init(&mut delay).unwrap();
+        display.define_scroll_area(&mut delay, ScrollArea::new(0, 240, 0)).unwrap();
+        let mut panel = Emulator::default();
+        panel.process(log.take());
+        assert_eq!(panel.scroll_area, rows, "{module:?} {rotation:?}");
+        assert_eq!(panel.scroll_start, rows.0);
+    }
+}
+
+#[test]
+fn scroll_areas_must_fit_a_portrait_display() {
+    let (display, _buffer, mut delay, log) = mock_display_in(PixelFormat::Rgb565);
+    let mut display = display.with_orientation(Orientation::new(Rotation::Deg90, false));
+    assert_eq!(display.define_scroll_area(&mut delay, ScrollArea::new(0, 240, 0)), Err(DisplayError::Uninitialized));
+    display.init(&mut delay).unwrap();
+    log.take();
+    // Landscape would scroll sideways
+    assert_eq!(display.define_scroll_area(&mut delay, ScrollArea::new(0, 240, 0)), Err(DisplayError::Region));
+    display.set_orientation(&mut delay, Orientation::new(Rotation::Deg0, false)).unwrap();
+    log.take();
+    for area in [ScrollArea::new(0, 300, 0), ScrollArea::new(100, 0, 220), ScrollArea::new(u16::MAX, 2, 2)] {
+        assert_eq!(display.define_scroll_area(&mut delay, area), Err(DisplayError::Region), "{area:?}");
+    }
+    assert_eq!(display.scroll_to(&mut delay, 1), Err(DisplayError::Region));
+    assert!(log.take().is_empty());
+}
+
+#[test]
+fn stopping_and_reorienting_return_to_the_plain_frame_memory() {
+    let (mut display, mut buffer, mut delay, log) = mock_display_in(PixelFormat::Rgb666);
+    display.init(&mut delay).unwrap();
+    buffer[..ROW_BYTES].fill(0xFC);
+    display.swap_buffers(&mut delay, &mut buffer).unwrap();
+    display.define_scroll_area(&mut delay, ScrollArea::new(0, HEIGHT, 0)).unwrap();
+    display.scroll_to(&mut delay, HEIGHT + 1).unwrap();
+    assert_eq!(display.scroll_offset(), 1);
+
+    let mut panel = Emulator::default();
+    panel.process(log.take());
+    assert!(panel.screen() != panel.image());
+    // The lit first row wrapped around to the bottom
+    assert!(row_of(&panel.screen(), PANEL_HEIGHT - 1).iter().all(|&c| c == 0xFC));
+
+    display.stop_scrolling(&mut delay).unwrap();
+    panel.process(log.take());
+    assert!(!panel.scrolling);
+    assert!(panel.screen() == panel.image());
+    assert_eq!(display.scroll_area(), None);
+
+    display.define_scroll_area(&mut delay, ScrollArea::new(0, HEIGHT, 0)).unwrap();
+    display.set_orientation(&mut delay, Orientation::new(Rotation::Deg90, false)).unwrap();
+    panel.process(log.take());
+    assert!(!panel.scrolling);
+    assert_eq!(display.scroll_area(), None);
+}
+
+#[test]
+fn log_views_send_one_line_per_step() {
+    const LINE_HEIGHT: u16 = 16;
+    const LINES: usize = 25;
+    let format = PixelFormat::Rgb666;
+    let area = ScrollArea::new(0, HEIGHT, 0);
+    assert_eq!(ScrollLog::new(area, WIDTH, 7), None);
+    let mut view = ScrollLog::new(area, WIDTH, LINE_HEIGHT).unwrap();
+
+    let (mut display, mut buffer, mut delay, log) = mock_display_in(format);
+    display.init(&mut delay).unwrap();
+    display.define_scroll_area(&mut delay, area).unwrap();
+    let mut panel = Emulator::default();
+    panel.process(log.take());
+    let written = panel.pixels_written;
+
+    for line in 0..LINES {
+        let region = view.next_line();
+        for pixel in buffer[..format.bytes_for(region.area())].chunks_exact_mut(3) {
+            pixel.copy_from_slice(&row_color(line));
+        }
+        display.swap_region(&mut delay, region, &mut buffer).unwrap();
+        let offset = view.advance();
+        display.scroll_to(&mut delay, offset).unwrap();
+    }
+    panel.process(log.take());
+    assert_eq!(panel.pixels_written - written, LINES * WIDTH as usize * LINE_HEIGHT as usize);
+
+    // The latest lines fill the screen top to bottom, the newest at the bottom
+    let screen = panel.screen();
+    let visible = HEIGHT as usize / LINE_HEIGHT as usize;
+    for (slot, line) in (LINES - visible..LINES).enumerate() {
+        for row in slot * LINE_HEIGHT as usize..(slot + 1) * LINE_HEIGHT as usize {
+            assert!(row_of(&screen, row).chunks_exact(3).all(|pixel| pixel == row_color(line)), "row {row}");
+        }
+    }
+}
+
+#[test]
+fn source_rows_cover_empty_and_tall_scroll_areas() {
+    assert_eq!(ScrollArea::new(16, 0, 16).source_row(0, 0), None);
+    let area = ScrollArea::new(0, u16::MAX, 0);
+    assert_eq!(area.source_row(u16::MAX - 1, 2), Some(1));
+    assert_eq!(ScrollArea::new(u16::MAX, 2, 0).source_row(1, 0), None);
+}
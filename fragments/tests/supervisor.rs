@@ -0,0 +1,83 @@
+//! Tests of the supervision of core 1
+
+use std::fmt;
+use std::panic::{self, AssertUnwindSafe};
+
+use fragments::supervisor::{FaultLog, Heartbeat, Recovery, RestartPolicy, StallDetector, FAULT_MESSAGE_LEN};
+
+#[test]
+fn a_stall_is_the_timeout_without_a_beat() {
+    let heartbeat = Heartbeat::new();
+    let mut detector = StallDetector::new(100);
+    // Time before arming does not count
+    detector.arm(1_000, &heartbeat);
+    assert!(!detector.stalled(1_099, &heartbeat));
+    heartbeat.beat();
+    assert!(!detector.stalled(1_099, &heartbeat));
+    assert!(!detector.stalled(1_198, &heartbeat));
+    assert!(detector.stalled(1_199, &heartbeat));
+
+    detector.arm(5_000, &heartbeat);
+    assert!(!detector.stalled(5_050, &heartbeat));
+    assert!(detector.stalled(5_100, &heartbeat));
+}
+
+#[test]
+fn the_first_fault_is_kept_until_taken() {
+    let log = FaultLog::new();
+    assert_eq!(log.get(), None);
+    assert!(log.record(1, format_args!("panicked at src/main.rs:{}:5:\nband out of range", 42)));
+    assert!(!log.record(0, format_args!("second fault")));
+
+    let fault = log.get().unwrap();
+    assert_eq!((fault.core, fault.message()), (1, "panicked at src/main.rs:42:5:\nband out of range"));
+    assert_eq!(log.take(), Some(fault));
+    assert_eq!(log.take(), None);
+    assert!(log.record(0, format_args!("second fault")));
+    assert_eq!(log.get().unwrap().core, 0);
+}
+
+/// Message whose formatting never finishes, as if its core was stopped while recording it
+struct Interrupted;
+
+impl fmt::Display for Interrupted {
+    fn fmt(&self, _: &mut fmt::Formatter<'_>) -> fmt::Result {
+        panic!("stopped while recording a fault");
+    }
+}
+
+#[test]
+fn an_unfinished_fault_can_be_abandoned() {
+    let log = FaultLog::new();
+    assert!(!log.abandon_unfinished());
+    let recording = panic::catch_unwind(AssertUnwindSafe(|| log.record(1, format_args!("{Interrupted}"))));
+    assert!(recording.is_err());
+    // The log stays blocked until the half-written fault is given up on
+    assert!(!log.record(0, format_args!("next fault")));
+    assert_eq!(log.get(), None);
+
+    assert!(log.abandon_unfinished());
+    assert!(log.record(0, format_args!("next fault")));
+    assert!(!log.abandon_unfinished());
+    assert_eq!(log.take().unwrap().message(), "next fault");
+}
+
+#[test]
+fn long_messages_are_cut_off_between_characters() {
+    let log = FaultLog::new();
+    let long = "é".repeat(FAULT_MESSAGE_LEN);
+    log.record(0, format_args!("x{long}"));
+    let message = log.take().unwrap().message().to_owned();
+    assert_eq!(message.len(), FAULT_MESSAGE_LEN - 1);
+    assert!(message.starts_with("xéé"));
+}
+
+#[test]
+fn a_failing_core_is_restarted_a_few_times_then_left_off() {
+    let mut policy = RestartPolicy::new(2);
+    assert_eq!(policy.on_failure(), Recovery::Restart);
+    assert_eq!(policy.on_failure(), Recovery::Restart);
+    assert_eq!(policy.on_failure(), Recovery::SingleCore);
+    assert_eq!(policy.on_failure(), Recovery::SingleCore);
+    assert_eq!(policy.restarts(), 2);
+}
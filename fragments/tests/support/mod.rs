@@ -0,0 +1,384 @@
+//! Mock hardware for driving the display driver on the host
+//!
+//! The SPI bus, the control pins and the delay all append to one shared event log, so that
+//! the exact interleaving of pin changes and bus writes can be replayed into the emulated panel.
+
+#![allow(dead_code)]
+
+pub mod st7789;
+
+use std::cell::RefCell;
+use std::future::Future;
+use std::pin::pin;
+use std::rc::Rc;
+use std::task::{Context, Poll, Waker};
+
+use embedded_hal::delay::DelayNs;
+use embedded_hal::digital::{self, OutputPin};
+use embedded_hal::pwm;
+use embedded_hal::spi::{self, SpiBus};
+use fragments::color::PixelFormat;
+use fragments::display::Display;
+#[cfg(feature = "async")]
+use fragments::display_async::AsyncDisplay;
+use fragments::dma::{DmaChannel, DmaTransfer};
+use fragments::panel::{PanelController, St7789};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Pin {
+    Cs,
+    Dc,
+    Rst,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Event {
+    Pin(Pin, bool),
+    Write(Vec<u8>),
+    /// The driver waited for the SPI bus to go idle
+    Flush,
+    Delay(u64),
+}
+
+#[derive(Default)]
+struct State {
+    events: Vec<Event>,
+    fail_spi: bool,
+    fail_pin: Option<Pin>,
+    dma_busy: bool,
+    dma_chaining: bool,
+    dma_starts: usize,
+}
+
+/// Shared, ordered record of everything the driver did to the hardware
+///
+/// Also injects faults: the next SPI write or pin change can be made to fail,
+/// and DMA transfers can be held in flight.
+#[derive(Clone, Default)]
+pub struct Log(Rc<RefCell<State>>);
+
+impl Log {
+    fn push(&self, event: Event) {
+        self.0.borrow_mut().events.push(event);
+    }
+
+    /// Remove and return all events recorded so far
+    pub fn take(&self) -> Vec<Event> {
+        std::mem::take(&mut self.0.borrow_mut().events)
+    }
+
+    /// Make the next SPI write fail
+    pub fn fail_next_spi_write(&self) {
+        self.0.borrow_mut().fail_spi = true;
+    }
+
+    /// Make the next change of `pin` fail
+    pub fn fail_next_pin_change(&self, pin: Pin) {
+        self.0.borrow_mut().fail_pin = Some(pin);
+    }
+
+    /// Keep DMA transfers from reporting completion
+    pub fn set_dma_busy(&self, busy: bool) {
+        self.0.borrow_mut().dma_busy = busy;
+    }
+
+    /// Let DMA transfers chain a second buffer behind the running one
+    pub fn set_dma_chaining(&self, chaining: bool) {
+        self.0.borrow_mut().dma_chaining = chaining;
+    }
+
+    /// DMA transfers started so far, not counting chained buffers
+    pub fn dma_starts(&self) -> usize {
+        self.0.borrow().dma_starts
+    }
+
+    /// Sum of all delays recorded so far, in nanoseconds
+    pub fn total_delay_ns(&self) -> u64 {
+        self.0
+            .borrow()
+            .events
+            .iter()
+            .map(|event| match event {
+                Event::Delay(ns) => *ns,
+                _ => 0,
+            })
+            .sum()
+    }
+}
+
+pub struct MockSpi(pub Log);
+
+impl spi::ErrorType for MockSpi {
+    type Error = spi::ErrorKind;
+}
+
+impl SpiBus for MockSpi {
+    fn read(&mut self, words: &mut [u8]) -> Result<(), Self::Error> {
+        words.fill(0);
+        Ok(())
+    }
+
+    fn write(&mut self, words: &[u8]) -> Result<(), Self::Error> {
+        if std::mem::take(&mut self.0.0.borrow_mut().fail_spi) {
+            return Err(spi::ErrorKind::Other);
+        }
+        self.0.push(Event::Write(words.to_vec()));
+        Ok(())
+    }
+
+    fn transfer(&mut self, read: &mut [u8], write: &[u8]) -> Result<(), Self::Error> {
+        self.write(write)?;
+        self.read(read)
+    }
+
+    fn transfer_in_place(&mut self, words: &mut [u8]) -> Result<(), Self::Error> {
+        self.write(words)?;
+        self.read(words)
+    }
+
+    fn flush(&mut self) -> Result<(), Self::Error> {
+        self.0.push(Event::Flush);
+        Ok(())
+    }
+}
+
+/// The mock bus completes every transfer immediately, asynchronously as well
+#[cfg(feature = "async")]
+impl embedded_hal_async::spi::SpiBus for MockSpi {
+    async fn read(&mut self, words: &mut [u8]) -> Result<(), Self::Error> {
+        SpiBus::read(self, words)
+    }
+
+    async fn write(&mut self, words: &[u8]) -> Result<(), Self::Error> {
+        SpiBus::write(self, words)
+    }
+
+    async fn transfer(&mut self, read: &mut [u8], write: &[u8]) -> Result<(), Self::Error> {
+        SpiBus::transfer(self, read, write)
+    }
+
+    async fn transfer_in_place(&mut self, words: &mut [u8]) -> Result<(), Self::Error> {
+        SpiBus::transfer_in_place(self, words)
+    }
+
+    async fn flush(&mut self) -> Result<(), Self::Error> {
+        SpiBus::flush(self)
+    }
+}
+
+pub struct MockPin(pub Pin, pub Log);
+
+impl MockPin {
+    fn set(&mut self, level: bool) -> Result<(), digital::ErrorKind> {
+        let mut state = self.1.0.borrow_mut();
+        if state.fail_pin == Some(self.0) {
+            state.fail_pin = None;
+            return Err(digital::ErrorKind::Other);
+        }
+        state.events.push(Event::Pin(self.0, level));
+        Ok(())
+    }
+}
+
+impl digital::ErrorType for MockPin {
+    type Error = digital::ErrorKind;
+}
+
+impl OutputPin for MockPin {
+    fn set_low(&mut self) -> Result<(), Self::Error> {
+        self.set(false)
+    }
+
+    fn set_high(&mut self) -> Result<(), Self::Error> {
+        self.set(true)
+    }
+}
+
+pub struct MockDelay(pub Log);
+
+impl DelayNs for MockDelay {
+    fn delay_ns(&mut self, ns: u32) {
+        self.0.push(Event::Delay(ns as u64));
+    }
+}
+
+#[cfg(feature = "async")]
+impl embedded_hal_async::delay::DelayNs for MockDelay {
+    async fn delay_ns(&mut self, ns: u32) {
+        DelayNs::delay_ns(self, ns);
+    }
+}
+
+/// PWM channel that records every duty cycle it is set to
+///
+/// Counts to 1000, so duty cycles read as tenths of a percent.
+#[derive(Clone, Default)]
+pub struct MockPwm {
+    pub duties: Rc<RefCell<Vec<u16>>>,
+    pub fail: Rc<RefCell<bool>>,
+}
+
+impl pwm::ErrorType for MockPwm {
+    type Error = pwm::ErrorKind;
+}
+
+impl pwm::SetDutyCycle for MockPwm {
+    fn max_duty_cycle(&self) -> u16 {
+        1000
+    }
+
+    fn set_duty_cycle(&mut self, duty: u16) -> Result<(), Self::Error> {
+        if std::mem::take(&mut *self.fail.borrow_mut()) {
+            return Err(pwm::ErrorKind::Other);
+        }
+        self.duties.borrow_mut().push(duty);
+        Ok(())
+    }
+}
+
+/// Run a future of the async driver to completion; the mocks never leave it pending
+pub fn block_on<F: Future>(future: F) -> F::Output {
+    let mut future = pin!(future);
+    let mut context = Context::from_waker(Waker::noop());
+    loop {
+        if let Poll::Ready(output) = future.as_mut().poll(&mut context) {
+            return output;
+        }
+    }
+}
+
+/// DMA channel that sends the buffer over the SPI bus as soon as a transfer starts
+///
+/// Raises its completion interrupt once the transfer is done, until it is acknowledged.
+#[derive(Default)]
+pub struct MockDma {
+    irq_pending: bool,
+}
+
+pub struct MockTransfer {
+    buffer: &'static mut [u8],
+    /// Buffer ahead of a chained `buffer`
+    sent: Option<&'static mut [u8]>,
+    spi: MockSpi,
+    acked: bool,
+}
+
+impl DmaChannel<MockSpi> for MockDma {
+    type Transfer = MockTransfer;
+
+    fn start(self, buffer: &'static mut [u8], len: usize, spi: MockSpi) -> Self::Transfer {
+        spi.0.push(Event::Write(buffer[..len].to_vec()));
+        spi.0.0.borrow_mut().dma_starts += 1;
+        MockTransfer { buffer, sent: None, spi, acked: false }
+    }
+
+    fn ack_irq(&mut self) -> bool {
+        core::mem::take(&mut self.irq_pending)
+    }
+}
+
+impl DmaTransfer<MockSpi> for MockTransfer {
+    type Channel = MockDma;
+
+    fn is_done(&self) -> bool {
+        !self.spi.0.0.borrow().dma_busy
+    }
+
+    fn ack_irq(&mut self) -> bool {
+        let raised = self.is_done() && !self.acked;
+        self.acked |= raised;
+        raised
+    }
+
+    fn chain(&mut self, buffer: &'static mut [u8], len: usize) -> Result<(), &'static mut [u8]> {
+        if !self.spi.0.0.borrow().dma_chaining || self.sent.is_some() {
+            return Err(buffer);
+        }
+        self.spi.0.push(Event::Write(buffer[..len].to_vec()));
+        self.sent = Some(std::mem::replace(&mut self.buffer, buffer));
+        Ok(())
+    }
+
+    fn take_sent(&mut self) -> Option<&'static mut [u8]> {
+        self.sent.take()
+    }
+
+    fn wait(self) -> (Self::Channel, &'static mut [u8], MockSpi) {
+        assert!(self.sent.is_none(), "a sent buffer was not taken back");
+        (MockDma { irq_pending: !self.acked }, self.buffer, self.spi)
+    }
+}
+
+pub type MockPanelDisplay<P> = Display<P, MockSpi, MockPin, MockPin, MockPin, MockDma>;
+pub type MockDisplay = MockPanelDisplay<St7789>;
+
+/// Create an RGB666 display driver on mock hardware together with the log it writes to
+///
+/// Returns the user's half of the double buffer as well.
+pub fn mock_display() -> (MockDisplay, &'static mut [u8], MockDelay, Log) {
+    mock_display_in(PixelFormat::Rgb666)
+}
+
+/// Create a display driver on mock hardware that sends frames in the given pixel format
+pub fn mock_display_in(format: PixelFormat) -> (MockDisplay, &'static mut [u8], MockDelay, Log) {
+    mock_display_with(format, St7789::LCD_240X320.buffer_size(format))
+}
+
+/// Create a display driver on mock hardware with two buffers of `size` bytes, e.g. strips
+pub fn mock_display_with(format: PixelFormat, size: usize) -> (MockDisplay, &'static mut [u8], MockDelay, Log) {
+    mock_panel_display_with(St7789::LCD_240X320, format, size)
+}
+
+/// Create a display driver for `panel` on mock hardware with full-frame buffers
+pub fn mock_panel_display<P: PanelController>(panel: P, format: PixelFormat) -> (MockPanelDisplay<P>, &'static mut [u8], MockDelay, Log) {
+    let size = panel.buffer_size(format);
+    mock_panel_display_with(panel, format, size)
+}
+
+fn mock_panel_display_with<P: PanelController>(
+    panel: P,
+    format: PixelFormat,
+    size: usize,
+) -> (MockPanelDisplay<P>, &'static mut [u8], MockDelay, Log) {
+    let log = Log::default();
+    let display = Display::new(
+        panel,
+        MockSpi(log.clone()),
+        MockPin(Pin::Cs, log.clone()),
+        MockPin(Pin::Dc, log.clone()),
+        MockPin(Pin::Rst, log.clone()),
+        MockDma::default(),
+        leak_buffer(size),
+    )
+    .with_pixel_format(format);
+    (display, leak_buffer(size), MockDelay(log.clone()), log)
+}
+
+/// Allocate a frame buffer that lives for the rest of the test
+pub fn leak_buffer(size: usize) -> &'static mut [u8] {
+    Box::leak(vec![0u8; size].into_boxed_slice())
+}
+
+#[cfg(feature = "async")]
+pub type MockAsyncDisplay<P = St7789> = AsyncDisplay<P, MockSpi, MockPin, MockPin, MockPin>;
+
+/// Create an async display driver on mock hardware that sends frames in the given pixel format
+#[cfg(feature = "async")]
+pub fn mock_async_display(format: PixelFormat) -> (MockAsyncDisplay, MockDelay, Log) {
+    mock_async_panel_display(St7789::LCD_240X320, format)
+}
+
+/// Create an async display driver for `panel` on mock hardware
+#[cfg(feature = "async")]
+pub fn mock_async_panel_display<P: PanelController>(panel: P, format: PixelFormat) -> (MockAsyncDisplay<P>, MockDelay, Log) {
+    let log = Log::default();
+    let display = AsyncDisplay::new(
+        panel,
+        MockSpi(log.clone()),
+        MockPin(Pin::Cs, log.clone()),
+        MockPin(Pin::Dc, log.clone()),
+        MockPin(Pin::Rst, log.clone()),
+    )
+    .with_pixel_format(format);
+    (display, MockDelay(log.clone()), log)
+}
@@ -0,0 +1,291 @@
+//! Emulated ST7789 controller
+//!
+//! Replays the recorded pin and bus events the way the panel interprets them and keeps the
+//! resulting controller state and frame memory (GRAM).
+
+use std::collections::HashMap;
+
+use super::{Event, Pin};
+
+pub const PANEL_WIDTH: usize = 240;
+pub const PANEL_HEIGHT: usize = 320;
+
+const SWRESET: u8 = 0x01;
+const SLPIN: u8 = 0x10;
+const SLPOUT: u8 = 0x11;
+const NORON: u8 = 0x13;
+const INVOFF: u8 = 0x20;
+const INVON: u8 = 0x21;
+const DISPOFF: u8 = 0x28;
+const DISPON: u8 = 0x29;
+const CASET: u8 = 0x2A;
+const RASET: u8 = 0x2B;
+const RAMWR: u8 = 0x2C;
+const VSCRDEF: u8 = 0x33;
+const MADCTL: u8 = 0x36;
+const VSCSAD: u8 = 0x37;
+const COLMOD: u8 = 0x3A;
+
+const MADCTL_MY: u8 = 0x80;
+const MADCTL_MX: u8 = 0x40;
+const MADCTL_MV: u8 = 0x20;
+
+pub struct St7789 {
+    cs: bool,
+    dc: bool,
+    rst: bool,
+    /// Elapsed time according to the delays the driver waited for, in nanoseconds
+    pub time_ns: u64,
+    pub sleeping: bool,
+    pub display_on: bool,
+    pub inverted: bool,
+    pub colmod: u8,
+    pub madctl: u8,
+    /// Column address window, inclusive
+    pub columns: (u16, u16),
+    /// Row address window, inclusive
+    pub rows: (u16, u16),
+    /// Fixed top, scroll and fixed bottom rows of GRAM, as set by VSCRDEF
+    pub scroll_area: (u16, u16, u16),
+    /// GRAM row shown at the top of the scroll area, as set by VSCSAD
+    pub scroll_start: u16,
+    /// Vertical scrolling mode, entered with VSCSAD and left with NORON
+    pub scrolling: bool,
+    /// All commands in the order they were received
+    pub commands: Vec<u8>,
+    /// Most recent parameters received for each command
+    pub parameters: HashMap<u8, Vec<u8>>,
+    /// Number of pixels written to GRAM
+    pub pixels_written: usize,
+    /// CS or DC changes while written bytes may still have been shifting out of the SPI peripheral
+    pub unflushed_pin_changes: usize,
+    bus_busy: bool,
+    command: Option<u8>,
+    cursor: (u16, u16),
+    pixel: Vec<u8>,
+    gram: Vec<[u8; 3]>,
+}
+
+impl Default for St7789 {
+    fn default() -> Self {
+        Self {
+            cs: true,
+            dc: true,
+            rst: true,
+            time_ns: 0,
+            sleeping: true,
+            display_on: false,
+            inverted: false,
+            colmod: 0x66,
+            madctl: 0x00,
+            columns: (0, PANEL_WIDTH as u16 - 1),
+            rows: (0, PANEL_HEIGHT as u16 - 1),
+            scroll_area: (0, PANEL_HEIGHT as u16, 0),
+            scroll_start: 0,
+            scrolling: false,
+            commands: Vec::new(),
+            parameters: HashMap::new(),
+            pixels_written: 0,
+            unflushed_pin_changes: 0,
+            bus_busy: false,
+            command: None,
+            cursor: (0, 0),
+            pixel: Vec::new(),
+            gram: vec![[0; 3]; PANEL_WIDTH * PANEL_HEIGHT],
+        }
+    }
+}
+
+impl St7789 {
+    /// Feed recorded events into the controller
+    pub fn process(&mut self, events: impl IntoIterator<Item = Event>) {
+        for event in events {
+            match event {
+                Event::Pin(Pin::Cs, level) => {
+                    self.check_flushed();
+                    self.cs = level;
+                }
+                Event::Pin(Pin::Dc, level) => {
+                    self.check_flushed();
+                    self.dc = level;
+                }
+                Event::Pin(Pin::Rst, level) => {
+                    if self.rst && !level {
+                        self.reset();
+                    }
+                    self.rst = level;
+                }
+                Event::Write(bytes) => {
+                    // Bytes are only clocked in while the controller is selected and out of reset
+                    if self.cs || !self.rst {
+                        continue;
+                    }
+                    self.bus_busy = true;
+                    for byte in bytes {
+                        if self.dc {
+                            self.data(byte);
+                        } else {
+                            self.command(byte);
+                        }
+                    }
+                }
+                Event::Flush => self.bus_busy = false,
+                Event::Delay(ns) => self.time_ns += ns,
+            }
+        }
+    }
+
+    /// GRAM contents as 8-bit RGB, in physical panel orientation
+    pub fn image(&self) -> Vec<u8> {
+        self.gram.iter().flatten().copied().collect()
+    }
+
+    /// Image shown on the panel as 8-bit RGB, in physical panel orientation
+    ///
+    /// Unlike `image`, this applies vertical scrolling.
+    pub fn screen(&self) -> Vec<u8> {
+        let (top, height, _) = self.scroll_area;
+        (0..PANEL_HEIGHT as u16)
+            .flat_map(|row| {
+                let mut source = row;
+                if self.scrolling && row >= top && row < top + height {
+                    source = self.scroll_start + row - top;
+                    if source >= top + height {
+                        source -= height;
+                    }
+                }
+                let start = source as usize * PANEL_WIDTH;
+                self.gram[start..start + PANEL_WIDTH].iter().flatten().copied()
+            })
+            .collect()
+    }
+
+    /// Commands received since the most recent occurrence of `command`
+    pub fn commands_since(&self, command: u8) -> &[u8] {
+        let start = self.commands.iter().rposition(|&c| c == command).map_or(0, |i| i + 1);
+        &self.commands[start..]
+    }
+
+    fn check_flushed(&mut self) {
+        if self.bus_busy {
+            self.unflushed_pin_changes += 1;
+        }
+    }
+
+    fn reset(&mut self) {
+        let commands = std::mem::take(&mut self.commands);
+        let gram = std::mem::take(&mut self.gram);
+        *self = Self {
+            time_ns: self.time_ns,
+            unflushed_pin_changes: self.unflushed_pin_changes,
+            bus_busy: self.bus_busy,
+            commands,
+            gram,
+            ..Self::default()
+        };
+    }
+
+    fn command(&mut self, command: u8) {
+        self.commands.push(command);
+        self.command = Some(command);
+        self.parameters.insert(command, Vec::new());
+        match command {
+            SWRESET => self.reset(),
+            SLPIN => self.sleeping = true,
+            SLPOUT => self.sleeping = false,
+            NORON => self.scrolling = false,
+            INVOFF => self.inverted = false,
+            INVON => self.inverted = true,
+            DISPOFF => self.display_on = false,
+            DISPON => self.display_on = true,
+            RAMWR => {
+                self.cursor = (self.columns.0, self.rows.0);
+                self.pixel.clear();
+            }
+            _ => {}
+        }
+    }
+
+    fn data(&mut self, byte: u8) {
+        let Some(command) = self.command else {
+            return;
+        };
+        if command == RAMWR {
+            self.memory_write(byte);
+            return;
+        }
+        let parameters = self.parameters.entry(command).or_default();
+        parameters.push(byte);
+        let p = parameters.clone();
+        match (command, p.len()) {
+            (CASET, 4) => self.columns = (u16::from_be_bytes([p[0], p[1]]), u16::from_be_bytes([p[2], p[3]])),
+            (RASET, 4) => self.rows = (u16::from_be_bytes([p[0], p[1]]), u16::from_be_bytes([p[2], p[3]])),
+            (MADCTL, 1) => self.madctl = p[0],
+            (COLMOD, 1) => self.colmod = p[0],
+            (VSCRDEF, 6) => {
+                let [top, height, bottom] = [0, 2, 4].map(|i| u16::from_be_bytes([p[i], p[i + 1]]));
+                self.scroll_area = (top, height, bottom);
+            }
+            (VSCSAD, 2) => {
+                self.scroll_start = u16::from_be_bytes([p[0], p[1]]);
+                self.scrolling = true;
+            }
+            _ => {}
+        }
+    }
+
+    /// Collect the bytes of the next pixel, or pixel pair for 12 bits per pixel, as set by COLMOD
+    fn memory_write(&mut self, byte: u8) {
+        self.pixel.push(byte);
+        let p = &self.pixel;
+        match (self.colmod & 0x07, p.len()) {
+            (0x03, 3) => {
+                let [a, b, c] = [p[0], p[1], p[2]];
+                self.pixel.clear();
+                self.store_pixel([a & 0xF0, a << 4, b & 0xF0]);
+                self.store_pixel([b << 4, c & 0xF0, c << 4]);
+            }
+            (0x05, 2) => {
+                let value = u16::from_be_bytes([p[0], p[1]]);
+                self.pixel.clear();
+                self.store_pixel([(value >> 8) as u8 & 0xF8, (value >> 3) as u8 & 0xFC, (value << 3) as u8]);
+            }
+            (0x06, 3) => {
+                let rgb = [p[0] & 0xFC, p[1] & 0xFC, p[2] & 0xFC];
+                self.pixel.clear();
+                self.store_pixel(rgb);
+            }
+            _ => {}
+        }
+    }
+
+    fn store_pixel(&mut self, rgb: [u8; 3]) {
+        let (column, row) = self.cursor;
+        if let Some(index) = self.gram_index(column as usize, row as usize) {
+            self.gram[index] = rgb;
+        }
+        self.pixels_written += 1;
+
+        // Advance within the window, wrapping back to its first row at the end
+        self.cursor.0 += 1;
+        if self.cursor.0 > self.columns.1 {
+            self.cursor.0 = self.columns.0;
+            self.cursor.1 = if self.cursor.1 >= self.rows.1 { self.rows.0 } else { self.cursor.1 + 1 };
+        }
+    }
+
+    /// Map a column/row address to a GRAM index, applying row/column exchange and mirroring
+    fn gram_index(&self, column: usize, row: usize) -> Option<usize> {
+        let (mut x, mut y) = if self.madctl & MADCTL_MV != 0 { (row, column) } else { (column, row) };
+        if x >= PANEL_WIDTH || y >= PANEL_HEIGHT {
+            return None;
+        }
+        if self.madctl & MADCTL_MX != 0 {
+            x = PANEL_WIDTH - 1 - x;
+        }
+        if self.madctl & MADCTL_MY != 0 {
+            y = PANEL_HEIGHT - 1 - y;
+        }
+        Some(y * PANEL_WIDTH + x)
+    }
+}
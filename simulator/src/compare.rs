@@ -0,0 +1,64 @@
+//! Image comparison for golden-image tests
+
+/// How far a rendered image may deviate from its reference
+#[derive(Clone, Copy, Debug)]
+pub struct Tolerance {
+    /// Largest difference of a single color channel that still counts as a match
+    pub max_channel_delta: u8,
+    /// Fraction of pixels that may exceed `max_channel_delta`
+    pub max_mismatch_ratio: f64,
+}
+
+impl Default for Tolerance {
+    /// Accept one RGB666 quantization step on every pixel and no further deviation
+    fn default() -> Self {
+        Self {
+            max_channel_delta: 4,
+            max_mismatch_ratio: 0.0,
+        }
+    }
+}
+
+/// Result of comparing two equally sized 8-bit RGB images
+#[derive(Clone, Debug)]
+pub struct Comparison {
+    /// Number of pixels with a channel difference above the tolerance
+    pub mismatched_pixels: usize,
+    /// Total number of pixels compared
+    pub total_pixels: usize,
+    /// Largest channel difference found anywhere in the image
+    pub max_channel_delta: u8,
+    /// Visualization of the differences: mismatches in red on a darkened copy of the expected image
+    pub diff: Vec<u8>,
+}
+
+impl Comparison {
+    /// Whether the deviation stays within `tolerance`
+    pub fn passes(&self, tolerance: &Tolerance) -> bool {
+        self.mismatched_pixels as f64 <= tolerance.max_mismatch_ratio * self.total_pixels as f64
+    }
+}
+
+/// Compare `actual` against `expected` pixel by pixel
+///
+/// Panics if the images differ in size.
+pub fn compare(expected: &[u8], actual: &[u8], tolerance: &Tolerance) -> Comparison {
+    assert_eq!(expected.len(), actual.len(), "images differ in size");
+    let mut comparison = Comparison {
+        mismatched_pixels: 0,
+        total_pixels: expected.len() / 3,
+        max_channel_delta: 0,
+        diff: Vec::with_capacity(expected.len()),
+    };
+    for (e, a) in expected.chunks_exact(3).zip(actual.chunks_exact(3)) {
+        let delta = e.iter().zip(a).map(|(e, a)| e.abs_diff(*a)).max().unwrap_or(0);
+        comparison.max_channel_delta = comparison.max_channel_delta.max(delta);
+        if delta > tolerance.max_channel_delta {
+            comparison.mismatched_pixels += 1;
+            comparison.diff.extend_from_slice(&[255, 0, 0]);
+        } else {
+            comparison.diff.extend(e.iter().map(|c| c / 4));
+        }
+    }
+    comparison
+}
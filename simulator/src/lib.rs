@@ -0,0 +1,36 @@
+//! Headless host renderer for fragment shaders
+//!
+//! Frames are rendered with the same code path the firmware uses to fill its frame buffers,
+//! so the output shows exactly what is sent to the panel, including the precision lost to the pixel format.
+
+pub mod compare;
+pub mod output;
+
+use fragments::color::PixelFormat;
+use fragments::framebuffer::{buffer_size, HEIGHT, WIDTH};
+use fragments::shader::{render_rows, FragmentShader};
+use fragments::uniforms::{FrameClock, InputState, Uniforms};
+
+/// Render one full frame into a newly allocated frame buffer in the given pixel format
+pub fn render_frame(shader: &(dyn FragmentShader + Sync), uniforms: &Uniforms, format: PixelFormat) -> Vec<u8> {
+    let mut buffer = vec![0u8; buffer_size(format)];
+    render_rows(shader, uniforms, format, &mut buffer, WIDTH as usize, HEIGHT as usize, 0..HEIGHT as usize);
+    buffer
+}
+
+/// Convert a full-screen frame buffer to 8-bit RGB as displayed by the panel
+pub fn to_rgb(format: PixelFormat, buffer: &[u8]) -> Vec<u8> {
+    let mut rgb = vec![0u8; 3 * WIDTH as usize * HEIGHT as usize];
+    format.decode(buffer, &mut rgb);
+    rgb
+}
+
+/// Uniforms of consecutive frames rendered at a constant frame rate without user input
+pub fn frame_uniforms(fps: f32) -> impl Iterator<Item = Uniforms> {
+    let frame_us = 1e6f64 / fps as f64;
+    let mut clock = FrameClock::new(0);
+    (0u64..).map(move |frame| {
+        let now_us = (frame as f64 * frame_us).round() as u64;
+        clock.next_frame(now_us, WIDTH, HEIGHT, InputState::default())
+    })
+}
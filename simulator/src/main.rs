@@ -0,0 +1,116 @@
+//! Render a shader on the host and write the frames as image files
+
+use std::path::PathBuf;
+use std::process::ExitCode;
+use std::{env, fs};
+
+use fragments::color::PixelFormat;
+use fragments::framebuffer::{HEIGHT, WIDTH};
+use fragments::shaders;
+use simulator::output::{write_png, write_ppm, GifWriter};
+use simulator::{frame_uniforms, render_frame, to_rgb};
+
+const USAGE: &str = "Usage: simulator [--shader NAME] [--frames N] [--fps FPS] [--pixel-format rgb444|rgb565|rgb666] [--format ppm|png|gif] [--out DIR]";
+
+#[derive(Clone, Copy, PartialEq)]
+enum Format {
+    Ppm,
+    Png,
+    Gif,
+}
+
+struct Options {
+    shader: String,
+    frames: usize,
+    fps: f32,
+    pixel_format: PixelFormat,
+    format: Format,
+    out: PathBuf,
+}
+
+fn parse_options() -> Result<Options, String> {
+    let mut options = Options {
+        shader: String::from("gradient"),
+        frames: 60,
+        fps: 30.0,
+        pixel_format: PixelFormat::Rgb565,
+        format: Format::Png,
+        out: PathBuf::from("frames"),
+    };
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().ok_or_else(|| format!("missing value for {}", arg));
+        match arg.as_str() {
+            "--shader" => options.shader = value()?,
+            "--frames" => options.frames = value()?.parse().map_err(|_| "invalid frame count")?,
+            "--fps" => options.fps = value()?.parse().map_err(|_| "invalid frame rate")?,
+            "--pixel-format" => {
+                options.pixel_format = match value()?.as_str() {
+                    "rgb444" => PixelFormat::Rgb444,
+                    "rgb565" => PixelFormat::Rgb565,
+                    "rgb666" => PixelFormat::Rgb666,
+                    other => return Err(format!("unknown pixel format {}", other)),
+                }
+            }
+            "--format" => {
+                options.format = match value()?.as_str() {
+                    "ppm" => Format::Ppm,
+                    "png" => Format::Png,
+                    "gif" => Format::Gif,
+                    other => return Err(format!("unknown format {}", other)),
+                }
+            }
+            "--out" => options.out = PathBuf::from(value()?),
+            other => return Err(format!("unknown argument {}", other)),
+        }
+    }
+    if options.fps <= 0.0 {
+        return Err(String::from("frame rate must be positive"));
+    }
+    Ok(options)
+}
+
+fn run(options: &Options) -> Result<(), String> {
+    let shader = shaders::by_name(&options.shader).ok_or_else(|| {
+        let names: Vec<_> = shaders::ALL.iter().map(|(name, _)| *name).collect();
+        format!("unknown shader {}, available: {}", options.shader, names.join(", "))
+    })?;
+    fs::create_dir_all(&options.out).map_err(|e| e.to_string())?;
+
+    let mut gif = match options.format {
+        Format::Gif => {
+            let path = options.out.join(format!("{}.gif", options.shader));
+            Some(GifWriter::create(&path, WIDTH, HEIGHT, options.fps).map_err(|e| e.to_string())?)
+        }
+        _ => None,
+    };
+
+    for uniforms in frame_uniforms(options.fps).take(options.frames) {
+        let frame = to_rgb(options.pixel_format, &render_frame(shader, &uniforms, options.pixel_format));
+        let name = format!("{}_{:04}", options.shader, uniforms.frame);
+        let result = match options.format {
+            Format::Ppm => write_ppm(&options.out.join(name + ".ppm"), WIDTH, HEIGHT, &frame),
+            Format::Png => write_png(&options.out.join(name + ".png"), WIDTH, HEIGHT, &frame),
+            Format::Gif => gif.as_mut().unwrap().write_frame(&frame),
+        };
+        result.map_err(|e| e.to_string())?;
+    }
+    Ok(())
+}
+
+fn main() -> ExitCode {
+    let options = match parse_options() {
+        Ok(options) => options,
+        Err(message) => {
+            eprintln!("{}\n{}", message, USAGE);
+            return ExitCode::FAILURE;
+        }
+    };
+    match run(&options) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(message) => {
+            eprintln!("error: {}", message);
+            ExitCode::FAILURE
+        }
+    }
+}
@@ -0,0 +1,66 @@
+//! Image file writers for rendered frames
+//!
+//! All writers take tightly packed 8-bit RGB pixel data, row by row.
+
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Write};
+use std::path::Path;
+
+/// Write a binary PPM (P6) image
+pub fn write_ppm(path: &Path, width: u16, height: u16, rgb: &[u8]) -> io::Result<()> {
+    let mut file = BufWriter::new(File::create(path)?);
+    write!(file, "P6\n{} {}\n255\n", width, height)?;
+    file.write_all(rgb)?;
+    file.flush()
+}
+
+/// Write an 8-bit RGB PNG image
+pub fn write_png(path: &Path, width: u16, height: u16, rgb: &[u8]) -> io::Result<()> {
+    let file = BufWriter::new(File::create(path)?);
+    let mut encoder = png::Encoder::new(file, width as u32, height as u32);
+    encoder.set_color(png::ColorType::Rgb);
+    encoder.set_depth(png::BitDepth::Eight);
+    let mut writer = encoder.write_header().map_err(io::Error::other)?;
+    writer.write_image_data(rgb).map_err(io::Error::other)?;
+    writer.finish().map_err(io::Error::other)
+}
+
+/// Read an 8-bit RGB PNG image, returning its width, height and pixel data
+pub fn read_png(path: &Path) -> io::Result<(u16, u16, Vec<u8>)> {
+    let decoder = png::Decoder::new(BufReader::new(File::open(path)?));
+    let mut reader = decoder.read_info().map_err(io::Error::other)?;
+    let mut rgb = vec![0u8; reader.output_buffer_size().unwrap_or(0)];
+    let info = reader.next_frame(&mut rgb).map_err(io::Error::other)?;
+    if info.color_type != png::ColorType::Rgb || info.bit_depth != png::BitDepth::Eight {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "expected an 8-bit RGB image"));
+    }
+    rgb.truncate(info.buffer_size());
+    Ok((info.width as u16, info.height as u16, rgb))
+}
+
+/// Animated GIF that loops forever
+pub struct GifWriter {
+    encoder: gif::Encoder<BufWriter<File>>,
+    width: u16,
+    height: u16,
+    delay: u16,
+}
+
+impl GifWriter {
+    /// Create the file and write the header of an animation running at `fps` frames per second
+    pub fn create(path: &Path, width: u16, height: u16, fps: f32) -> io::Result<Self> {
+        let file = BufWriter::new(File::create(path)?);
+        let mut encoder = gif::Encoder::new(file, width, height, &[]).map_err(io::Error::other)?;
+        encoder.set_repeat(gif::Repeat::Infinite).map_err(io::Error::other)?;
+        // GIF frame delays are given in hundredths of a second
+        let delay = (100.0f32 / fps).round().max(1.0) as u16;
+        Ok(Self { encoder, width, height, delay })
+    }
+
+    /// Append a frame to the animation
+    pub fn write_frame(&mut self, rgb: &[u8]) -> io::Result<()> {
+        let mut frame = gif::Frame::from_rgb_speed(self.width, self.height, rgb, 10);
+        frame.delay = self.delay;
+        self.encoder.write_frame(&frame).map_err(io::Error::other)
+    }
+}
@@ -0,0 +1,86 @@
+//! Golden-image regression tests for all shipped shaders
+//!
+//! Each shader is rendered at fixed frames and compared against the reference images in
+//! `tests/golden`. On a mismatch, a diff image is written next to the rendered frame in the
+//! cargo target directory. Run with `UPDATE_GOLDEN=1` to (re)create the reference images.
+
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use fragments::color::PixelFormat;
+use fragments::framebuffer::{HEIGHT, WIDTH};
+use fragments::shaders;
+use simulator::compare::{compare, Tolerance};
+use simulator::output::{read_png, write_png};
+use simulator::{frame_uniforms, render_frame, to_rgb};
+
+const FPS: f32 = 30.0;
+const FRAMES: [usize; 3] = [0, 45, 150];
+/// Pixel formats with the size of one quantization step, the largest accepted channel difference
+const FORMATS: [(&str, PixelFormat, u8); 3] = [
+    ("rgb444", PixelFormat::Rgb444, 16),
+    ("rgb565", PixelFormat::Rgb565, 8),
+    ("rgb666", PixelFormat::Rgb666, 4),
+];
+
+fn golden_dir() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("tests").join("golden")
+}
+
+fn failure_dir() -> PathBuf {
+    Path::new(env!("CARGO_TARGET_TMPDIR")).join("golden")
+}
+
+#[test]
+fn shaders_match_golden_images() {
+    let update = env::var_os("UPDATE_GOLDEN").is_some();
+    let mut failures = Vec::new();
+
+    for (name, shader) in shaders::ALL {
+        let mut uniforms = frame_uniforms(FPS);
+        let mut next_frame = 0;
+        for frame in FRAMES {
+            let uniforms = uniforms.nth(frame - next_frame).unwrap();
+            next_frame = frame + 1;
+            for (format_name, format, step) in FORMATS {
+                let tolerance = Tolerance { max_channel_delta: step, ..Tolerance::default() };
+                let actual = to_rgb(format, &render_frame(*shader, &uniforms, format));
+                let file_name = format!("{}_{}_{:04}.png", name, format_name, frame);
+                let reference = golden_dir().join(&file_name);
+
+                if update {
+                    write_png(&reference, WIDTH, HEIGHT, &actual).unwrap();
+                    continue;
+                }
+                let Ok((width, height, expected)) = read_png(&reference) else {
+                    failures.push(format!("{}: missing reference image, run with UPDATE_GOLDEN=1", file_name));
+                    continue;
+                };
+                if (width, height) != (WIDTH, HEIGHT) {
+                    failures.push(format!("{}: reference is {}x{}", file_name, width, height));
+                    continue;
+                }
+
+                let comparison = compare(&expected, &actual, &tolerance);
+                if !comparison.passes(&tolerance) {
+                    fs::create_dir_all(failure_dir()).unwrap();
+                    let actual_path = failure_dir().join(&file_name);
+                    let diff_path = failure_dir().join(format!("{}_{}_{:04}_diff.png", name, format_name, frame));
+                    write_png(&actual_path, WIDTH, HEIGHT, &actual).unwrap();
+                    write_png(&diff_path, WIDTH, HEIGHT, &comparison.diff).unwrap();
+                    failures.push(format!(
+                        "{}: {} of {} pixels differ (max channel delta {}), see {}",
+                        file_name,
+                        comparison.mismatched_pixels,
+                        comparison.total_pixels,
+                        comparison.max_channel_delta,
+                        diff_path.display(),
+                    ));
+                }
+            }
+        }
+    }
+
+    assert!(failures.is_empty(), "golden image mismatches:\n{}", failures.join("\n"));
+}